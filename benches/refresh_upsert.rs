@@ -0,0 +1,254 @@
+//! Times the refresh write path: the old per-row select-then-write against
+//! the chunked writers in `repository`, from the country upsert through
+//! currencies and history to the changelog. Every run happens in a
+//! rolled-back transaction, but migrations are applied first, so the bench
+//! only runs against a scratch database named by `BENCH_DATABASE_URL` and
+//! never falls back to `DATABASE_URL`.
+//!
+//!     BENCH_DATABASE_URL=mysql://.../bench cargo bench --bench refresh_upsert
+//!
+//! With one currency per country, a pass over 250 countries costs 1500
+//! statements per-row (lookup, write, currency delete and insert, history
+//! and changelog rows for each country) against 18 chunked (five per chunk
+//! of 100 for the countries, currencies and history, plus three changelog
+//! inserts). Wall-clock timings depend on the round-trip latency to the
+//! server, which is what the chunking removes.
+
+use chrono::Utc;
+use hng_backend_2::db::repository;
+use hng_backend_2::models::{CountryCurrency, CountryDiff, CountryInsert, FieldChange, RefreshDiff};
+use sqlx::mysql::MySqlPoolOptions;
+use sqlx::{MySql, Pool, Transaction};
+use std::time::{Duration, Instant};
+
+const COUNTRIES: usize = 250;
+const ITERATIONS: u32 = 5;
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+
+    let Ok(database_url) = std::env::var("BENCH_DATABASE_URL") else {
+        eprintln!("BENCH_DATABASE_URL is not set, refusing to run refresh_upsert benchmark");
+        eprintln!("Point it at a scratch database; migrations are applied to it.");
+        std::process::exit(1);
+    };
+
+    if std::env::var("DATABASE_URL").is_ok_and(|url| url == database_url) {
+        eprintln!("BENCH_DATABASE_URL is the same as DATABASE_URL, refusing to run refresh_upsert benchmark");
+        std::process::exit(1);
+    }
+
+    let pool = MySqlPoolOptions::new()
+        .max_connections(1)
+        .connect(&database_url)
+        .await
+        .expect("Failed to connect to BENCH_DATABASE_URL");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("Failed to run migrations");
+
+    let countries = sample_countries();
+    let changes = sample_changes(&countries);
+
+    let per_row = measure(&pool, &countries, &changes, Strategy::PerRow).await;
+    let batched = measure(&pool, &countries, &changes, Strategy::Batched).await;
+
+    println!("{} countries, written twice (insert then update), mean of {} runs", COUNTRIES, ITERATIONS);
+    println!("  per-row select-then-write: {:>8.1} ms", per_row.as_secs_f64() * 1000.0);
+    println!("  chunked upsert:            {:>8.1} ms", batched.as_secs_f64() * 1000.0);
+    println!("  speedup:                   {:>8.1}x", per_row.as_secs_f64() / batched.as_secs_f64());
+}
+
+#[derive(Clone, Copy)]
+enum Strategy {
+    PerRow,
+    Batched,
+}
+
+async fn measure(
+    pool: &Pool<MySql>,
+    countries: &[CountryInsert],
+    changes: &RefreshDiff,
+    strategy: Strategy,
+) -> Duration {
+    let mut total = Duration::ZERO;
+
+    for _ in 0..ITERATIONS {
+        let mut tx = pool.begin().await.expect("Failed to begin transaction");
+        let started = Instant::now();
+
+        // The second pass hits the update branch for every country.
+        for _ in 0..2 {
+            match strategy {
+                Strategy::PerRow => write_per_row(&mut tx, countries, changes).await,
+                Strategy::Batched => write_batched(&mut tx, countries, changes).await,
+            }
+        }
+
+        total += started.elapsed();
+        tx.rollback().await.expect("Failed to roll back transaction");
+    }
+
+    total / ITERATIONS
+}
+
+// The refresh's write loop, one chunk at a time, then its changelog.
+async fn write_batched(tx: &mut Transaction<'_, MySql>, countries: &[CountryInsert], changes: &RefreshDiff) {
+    for chunk in countries.chunks(repository::UPSERT_CHUNK_SIZE) {
+        let ids = repository::upsert_countries(tx, chunk).await.unwrap();
+        let written: Vec<(u64, &CountryInsert)> = ids.into_iter().zip(chunk).collect();
+
+        repository::replace_currencies_batch(tx, &written).await.unwrap();
+        repository::insert_history_batch(tx, 0, &written, Utc::now()).await.unwrap();
+    }
+
+    repository::insert_refresh_changes(tx, 0, changes).await.unwrap();
+}
+
+// The statements the refresh used to issue for each country.
+async fn write_per_row(tx: &mut Transaction<'_, MySql>, countries: &[CountryInsert], changes: &RefreshDiff) {
+    for country in countries {
+        let existing: Option<u64> = sqlx::query_scalar("SELECT id FROM countries WHERE LOWER(name) = LOWER(?)")
+            .bind(&country.name)
+            .fetch_optional(&mut **tx)
+            .await
+            .unwrap();
+
+        let country_id = match existing {
+            Some(id) => {
+                sqlx::query(
+                    "UPDATE countries SET capital = ?, region = ?, population = ?, currency_code = ?, exchange_rate = ?, estimated_gdp = ?, gdp_model = ?, flag_url = ?, last_refreshed_at = ?, stale_since = NULL WHERE LOWER(name) = LOWER(?)"
+                )
+                .bind(&country.capital)
+                .bind(&country.region)
+                .bind(country.population)
+                .bind(&country.currency_code)
+                .bind(country.exchange_rate)
+                .bind(country.estimated_gdp)
+                .bind(&country.gdp_model)
+                .bind(&country.flag_url)
+                .bind(Utc::now())
+                .bind(&country.name)
+                .execute(&mut **tx)
+                .await
+                .unwrap();
+                id
+            }
+            None => sqlx::query(
+                "INSERT INTO countries (name, capital, region, population, currency_code, exchange_rate, estimated_gdp, gdp_model, flag_url, last_refreshed_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&country.name)
+            .bind(&country.capital)
+            .bind(&country.region)
+            .bind(country.population)
+            .bind(&country.currency_code)
+            .bind(country.exchange_rate)
+            .bind(country.estimated_gdp)
+            .bind(&country.gdp_model)
+            .bind(&country.flag_url)
+            .bind(Utc::now())
+            .execute(&mut **tx)
+            .await
+            .unwrap()
+            .last_insert_id(),
+        };
+
+        sqlx::query("DELETE FROM country_currencies WHERE country_id = ?")
+            .bind(country_id)
+            .execute(&mut **tx)
+            .await
+            .unwrap();
+
+        for currency in &country.currencies {
+            sqlx::query(
+                "INSERT INTO country_currencies (country_id, code, name, symbol, is_primary) VALUES (?, ?, ?, ?, ?)"
+            )
+            .bind(country_id)
+            .bind(&currency.code)
+            .bind(&currency.name)
+            .bind(&currency.symbol)
+            .bind(currency.is_primary)
+            .execute(&mut **tx)
+            .await
+            .unwrap();
+        }
+
+        sqlx::query(
+            "INSERT INTO country_history (refresh_job_id, country_id, country_name, population, exchange_rate, estimated_gdp, recorded_at) VALUES (?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(0u64)
+        .bind(country_id)
+        .bind(&country.name)
+        .bind(country.population)
+        .bind(country.exchange_rate)
+        .bind(country.estimated_gdp)
+        .bind(Utc::now())
+        .execute(&mut **tx)
+        .await
+        .unwrap();
+    }
+
+    for country in &changes.changed {
+        sqlx::query(
+            "INSERT INTO refresh_changes (refresh_job_id, country_name, change_type, changes) VALUES (?, ?, ?, ?)"
+        )
+        .bind(0u64)
+        .bind(&country.name)
+        .bind("changed")
+        .bind(serde_json::to_string(&country.changes).unwrap())
+        .execute(&mut **tx)
+        .await
+        .unwrap();
+    }
+}
+
+fn sample_countries() -> Vec<CountryInsert> {
+    (0..COUNTRIES)
+        .map(|i| {
+            let code = format!("B{:02}", i % 100);
+            CountryInsert {
+                name: format!("Benchmark Country {}", i),
+                capital: Some(format!("Capital {}", i)),
+                region: Some("Benchmark".to_string()),
+                population: 1_000_000 + i as i64,
+                currency_code: Some(code.clone()),
+                exchange_rate: Some(1.0 + i as f64),
+                estimated_gdp: Some(1_500_000_000.0 / (1.0 + i as f64)),
+                gdp_model: Some("fixed_multiplier".to_string()),
+                flag_url: None,
+                currencies: vec![CountryCurrency {
+                    code,
+                    name: None,
+                    symbol: None,
+                    is_primary: true,
+                }],
+            }
+        })
+        .collect()
+}
+
+// Every country's rate moved, as on most days.
+fn sample_changes(countries: &[CountryInsert]) -> RefreshDiff {
+    RefreshDiff {
+        reconcile_policy: "keep".to_string(),
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: countries
+            .iter()
+            .map(|country| CountryDiff {
+                name: country.name.clone(),
+                changes: [(
+                    "exchange_rate".to_string(),
+                    FieldChange {
+                        old: serde_json::json!(country.exchange_rate),
+                        new: serde_json::json!(country.exchange_rate.map(|rate| rate * 1.01)),
+                    },
+                )]
+                .into(),
+            })
+            .collect(),
+    }
+}
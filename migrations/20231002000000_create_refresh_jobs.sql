@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS refresh_jobs (
+    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+    status VARCHAR(20) NOT NULL,
+    total_countries INT NOT NULL DEFAULT 0,
+    countries_processed INT NOT NULL DEFAULT 0,
+    error TEXT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    started_at DATETIME NULL,
+    finished_at DATETIME NULL,
+    INDEX idx_status (status)
+);
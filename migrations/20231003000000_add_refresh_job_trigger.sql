@@ -0,0 +1,3 @@
+ALTER TABLE refresh_jobs
+    ADD COLUMN triggered_by VARCHAR(20) NOT NULL DEFAULT 'manual' AFTER status,
+    ADD INDEX idx_triggered_by (triggered_by);
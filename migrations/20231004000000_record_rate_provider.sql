@@ -0,0 +1,7 @@
+ALTER TABLE refresh_jobs
+    ADD COLUMN rate_provider VARCHAR(255) NULL,
+    ADD COLUMN rates_as_of DATETIME NULL;
+
+ALTER TABLE refresh_metadata
+    ADD COLUMN rate_provider VARCHAR(255) NULL,
+    ADD COLUMN rates_as_of DATETIME NULL;
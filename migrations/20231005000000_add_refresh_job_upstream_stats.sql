@@ -0,0 +1,2 @@
+ALTER TABLE refresh_jobs
+    ADD COLUMN upstream_stats TEXT NULL AFTER rates_as_of;
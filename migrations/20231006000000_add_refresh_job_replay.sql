@@ -0,0 +1,2 @@
+ALTER TABLE refresh_jobs
+    ADD COLUMN replay_of BIGINT UNSIGNED NULL AFTER triggered_by;
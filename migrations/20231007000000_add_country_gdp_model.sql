@@ -0,0 +1,2 @@
+ALTER TABLE countries
+    ADD COLUMN gdp_model VARCHAR(50) NULL AFTER estimated_gdp;
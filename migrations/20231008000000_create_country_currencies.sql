@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS country_currencies (
+    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+    country_id BIGINT UNSIGNED NOT NULL,
+    code VARCHAR(10) NOT NULL,
+    name VARCHAR(255) NULL,
+    symbol VARCHAR(20) NULL,
+    is_primary BOOLEAN NOT NULL DEFAULT FALSE,
+    UNIQUE KEY uq_country_currency (country_id, code),
+    INDEX idx_code (code),
+    CONSTRAINT fk_country_currencies_country FOREIGN KEY (country_id) REFERENCES countries (id) ON DELETE CASCADE
+);
+
+INSERT INTO country_currencies (country_id, code, is_primary)
+SELECT id, currency_code, TRUE FROM countries WHERE currency_code IS NOT NULL;
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS country_history (
+    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+    refresh_job_id BIGINT UNSIGNED NOT NULL,
+    country_id BIGINT UNSIGNED NOT NULL,
+    country_name VARCHAR(255) NOT NULL,
+    population BIGINT NOT NULL,
+    exchange_rate DOUBLE NULL,
+    estimated_gdp DOUBLE NULL,
+    recorded_at DATETIME NOT NULL,
+    INDEX idx_country_name_recorded_at (country_name, recorded_at),
+    INDEX idx_refresh_job_id (refresh_job_id)
+);
@@ -0,0 +1,22 @@
+-- One row per currency per rate date, expressed as units of the currency per USD.
+CREATE TABLE IF NOT EXISTS exchange_rates (
+    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+    rate_date DATE NOT NULL,
+    currency_code VARCHAR(10) NOT NULL,
+    rate DOUBLE NOT NULL,
+    provider VARCHAR(255) NULL,
+    fetched_at DATETIME NOT NULL,
+    UNIQUE KEY uniq_rate_date_currency (rate_date, currency_code),
+    INDEX idx_currency_code_rate_date (currency_code, rate_date)
+);
+
+INSERT INTO exchange_rates (rate_date, currency_code, rate, provider, fetched_at)
+SELECT DATE(COALESCE(m.rates_as_of, m.last_refreshed_at)), c.currency_code, c.rate, m.rate_provider, m.last_refreshed_at
+FROM (
+    SELECT currency_code, MAX(exchange_rate) AS rate
+    FROM countries
+    WHERE currency_code IS NOT NULL AND exchange_rate IS NOT NULL
+    GROUP BY currency_code
+) c
+CROSS JOIN refresh_metadata m
+ON DUPLICATE KEY UPDATE rate = VALUES(rate);
@@ -0,0 +1,7 @@
+ALTER TABLE countries
+    ADD COLUMN stale_since DATETIME NULL,
+    ADD INDEX idx_stale_since (stale_since);
+
+ALTER TABLE refresh_jobs
+    ADD COLUMN reconcile_policy VARCHAR(16) NULL,
+    ADD COLUMN reconciled_countries TEXT NULL;
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS refresh_changes (
+    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+    refresh_job_id BIGINT UNSIGNED NOT NULL,
+    country_name VARCHAR(255) NOT NULL,
+    change_type VARCHAR(16) NOT NULL,
+    changes TEXT NULL,
+    INDEX idx_refresh_job_id (refresh_job_id)
+);
@@ -0,0 +1,12 @@
+-- Keyed by name rather than country ID so pinned values survive a country
+-- being deleted and later returned by the upstream again. NULL means the
+-- field is not overridden.
+CREATE TABLE IF NOT EXISTS country_overrides (
+    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+    country_name VARCHAR(255) NOT NULL UNIQUE,
+    capital VARCHAR(255) NULL,
+    region VARCHAR(100) NULL,
+    population BIGINT NULL,
+    flag_url TEXT NULL,
+    updated_at DATETIME NOT NULL
+);
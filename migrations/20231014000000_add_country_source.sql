@@ -0,0 +1,4 @@
+-- 'upstream' rows are owned by the refresh; 'manual' rows were created through
+-- the API and are left alone when missing from the upstream source.
+ALTER TABLE countries
+    ADD COLUMN source VARCHAR(16) NOT NULL DEFAULT 'upstream';
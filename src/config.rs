@@ -1,4 +1,93 @@
 use std::env;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CountryProviderKind {
+    RestCountriesV2,
+    RestCountriesV3,
+    StaticFile,
+}
+
+impl FromStr for CountryProviderKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "restcountries_v2" => Ok(CountryProviderKind::RestCountriesV2),
+            "restcountries_v3" => Ok(CountryProviderKind::RestCountriesV3),
+            "file" => Ok(CountryProviderKind::StaticFile),
+            other => Err(format!("unknown country provider: {}", other)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateProviderKind {
+    OpenErApi,
+    StaticFile,
+}
+
+impl FromStr for RateProviderKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "open_er_api" => Ok(RateProviderKind::OpenErApi),
+            "file" => Ok(RateProviderKind::StaticFile),
+            other => Err(format!("unknown rate provider: {}", other)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GdpModelKind {
+    SeededRandom,
+    FixedMultiplier,
+    PerCapitaCsv,
+}
+
+impl FromStr for GdpModelKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "seeded_random" => Ok(GdpModelKind::SeededRandom),
+            "fixed_multiplier" => Ok(GdpModelKind::FixedMultiplier),
+            "per_capita_csv" => Ok(GdpModelKind::PerCapitaCsv),
+            other => Err(format!("unknown GDP model: {}", other)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReconcilePolicy {
+    Keep,
+    Stale,
+    Delete,
+}
+
+impl ReconcilePolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReconcilePolicy::Keep => "keep",
+            ReconcilePolicy::Stale => "stale",
+            ReconcilePolicy::Delete => "delete",
+        }
+    }
+}
+
+impl FromStr for ReconcilePolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "keep" => Ok(ReconcilePolicy::Keep),
+            "stale" => Ok(ReconcilePolicy::Stale),
+            "delete" => Ok(ReconcilePolicy::Delete),
+            other => Err(format!("unknown reconcile policy: {}", other)),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Config {
@@ -6,10 +95,41 @@ pub struct Config {
     pub server_host: String,
     pub server_port: u16,
     pub external_api_timeout_secs: u64,
+    pub countries_api_timeout_secs: u64,
+    pub exchange_rates_api_timeout_secs: u64,
+    pub upstream_max_retries: u32,
+    pub upstream_retry_base_delay_ms: u64,
+    pub upstream_retry_max_delay_ms: u64,
+    pub circuit_breaker_threshold: u32,
+    pub circuit_breaker_cooldown_secs: u64,
+    pub upstream_cache_dir: String,
+    pub snapshot_dir: String,
+    pub snapshot_retention: usize,
+    pub countries_api_url: String,
+    pub exchange_rates_api_url: String,
+    pub country_providers: Vec<CountryProviderKind>,
+    pub rate_providers: Vec<RateProviderKind>,
+    pub stored_rates_fallback: bool,
+    pub countries_file: String,
+    pub exchange_rates_file: String,
+    pub gdp_model: GdpModelKind,
+    pub gdp_random_seed: u64,
+    pub gdp_random_per_refresh: bool,
+    pub gdp_fixed_multiplier: f64,
+    pub gdp_per_capita_csv: String,
+    pub refresh_interval_secs: Option<u64>,
+    pub refresh_cron: Option<String>,
+    pub refresh_jitter_secs: u64,
+    pub reconcile_policy: ReconcilePolicy,
 }
 
 impl Config {
     pub fn from_env() -> Self {
+        let external_api_timeout_secs = env::var("EXTERNAL_API_TIMEOUT_SECS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .expect("EXTERNAL_API_TIMEOUT_SECS must be a valid u64");
+
         Self {
             database_url: env::var("DATABASE_URL")
                 .expect("DATABASE_URL must be set"),
@@ -19,10 +139,103 @@ impl Config {
                 .unwrap_or_else(|_| "8080".to_string())
                 .parse()
                 .expect("SERVER_PORT must be a valid u16"),
-            external_api_timeout_secs: env::var("EXTERNAL_API_TIMEOUT_SECS")
-                .unwrap_or_else(|_| "30".to_string())
+            external_api_timeout_secs,
+            countries_api_timeout_secs: env::var("COUNTRIES_API_TIMEOUT_SECS")
+                .map(|v| v.parse().expect("COUNTRIES_API_TIMEOUT_SECS must be a valid u64"))
+                .unwrap_or(external_api_timeout_secs),
+            exchange_rates_api_timeout_secs: env::var("EXCHANGE_RATES_API_TIMEOUT_SECS")
+                .map(|v| v.parse().expect("EXCHANGE_RATES_API_TIMEOUT_SECS must be a valid u64"))
+                .unwrap_or(external_api_timeout_secs),
+            upstream_max_retries: env::var("UPSTREAM_MAX_RETRIES")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .expect("UPSTREAM_MAX_RETRIES must be a valid u32"),
+            upstream_retry_base_delay_ms: env::var("UPSTREAM_RETRY_BASE_DELAY_MS")
+                .unwrap_or_else(|_| "500".to_string())
+                .parse()
+                .expect("UPSTREAM_RETRY_BASE_DELAY_MS must be a valid u64"),
+            upstream_retry_max_delay_ms: env::var("UPSTREAM_RETRY_MAX_DELAY_MS")
+                .unwrap_or_else(|_| "10000".to_string())
+                .parse()
+                .expect("UPSTREAM_RETRY_MAX_DELAY_MS must be a valid u64"),
+            circuit_breaker_threshold: env::var("CIRCUIT_BREAKER_THRESHOLD")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .expect("CIRCUIT_BREAKER_THRESHOLD must be a valid u32"),
+            circuit_breaker_cooldown_secs: env::var("CIRCUIT_BREAKER_COOLDOWN_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .expect("CIRCUIT_BREAKER_COOLDOWN_SECS must be a valid u64"),
+            upstream_cache_dir: env::var("UPSTREAM_CACHE_DIR")
+                .unwrap_or_else(|_| "cache/upstream".to_string()),
+            snapshot_dir: env::var("SNAPSHOT_DIR")
+                .unwrap_or_else(|_| "cache/snapshots".to_string()),
+            snapshot_retention: env::var("SNAPSHOT_RETENTION")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse()
+                .expect("SNAPSHOT_RETENTION must be a valid usize"),
+            countries_api_url: env::var("COUNTRIES_API_URL")
+                .unwrap_or_else(|_| "https://restcountries.com".to_string()),
+            exchange_rates_api_url: env::var("EXCHANGE_RATES_API_URL")
+                .unwrap_or_else(|_| "https://open.er-api.com".to_string()),
+            country_providers: env::var("COUNTRY_PROVIDER")
+                .unwrap_or_else(|_| "restcountries_v2".to_string())
+                .split(',')
+                .map(|kind| {
+                    kind.trim()
+                        .parse()
+                        .expect("COUNTRY_PROVIDER must be a comma-separated list of restcountries_v2, restcountries_v3, file")
+                })
+                .collect(),
+            // RATE_PROVIDER is the single-provider name from before the list.
+            rate_providers: env::var("RATE_PROVIDERS")
+                .or_else(|_| env::var("RATE_PROVIDER"))
+                .unwrap_or_else(|_| "open_er_api".to_string())
+                .split(',')
+                .map(|kind| {
+                    kind.trim()
+                        .parse()
+                        .expect("RATE_PROVIDERS must be a comma-separated list of open_er_api, file")
+                })
+                .collect(),
+            stored_rates_fallback: env::var("RATE_FALLBACK_TO_STORED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .expect("RATE_FALLBACK_TO_STORED must be true or false"),
+            countries_file: env::var("COUNTRIES_FILE")
+                .unwrap_or_else(|_| "fixtures/countries.json".to_string()),
+            exchange_rates_file: env::var("EXCHANGE_RATES_FILE")
+                .unwrap_or_else(|_| "fixtures/exchange_rates.json".to_string()),
+            gdp_model: env::var("GDP_MODEL")
+                .unwrap_or_else(|_| "seeded_random".to_string())
+                .parse()
+                .expect("GDP_MODEL must be one of seeded_random, fixed_multiplier, per_capita_csv"),
+            gdp_random_seed: env::var("GDP_RANDOM_SEED")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("GDP_RANDOM_SEED must be a valid u64"),
+            gdp_random_per_refresh: env::var("GDP_RANDOM_PER_REFRESH")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .expect("GDP_RANDOM_PER_REFRESH must be true or false"),
+            gdp_fixed_multiplier: env::var("GDP_FIXED_MULTIPLIER")
+                .unwrap_or_else(|_| "1500".to_string())
+                .parse()
+                .expect("GDP_FIXED_MULTIPLIER must be a valid f64"),
+            gdp_per_capita_csv: env::var("GDP_PER_CAPITA_CSV")
+                .unwrap_or_else(|_| "fixtures/gdp_per_capita.csv".to_string()),
+            refresh_interval_secs: env::var("REFRESH_INTERVAL_SECS")
+                .ok()
+                .map(|v| v.parse().expect("REFRESH_INTERVAL_SECS must be a valid u64")),
+            refresh_cron: env::var("REFRESH_CRON").ok(),
+            refresh_jitter_secs: env::var("REFRESH_JITTER_SECS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("REFRESH_JITTER_SECS must be a valid u64"),
+            reconcile_policy: env::var("RECONCILE_POLICY")
+                .unwrap_or_else(|_| "keep".to_string())
                 .parse()
-                .expect("EXTERNAL_API_TIMEOUT_SECS must be a valid u64"),
+                .expect("RECONCILE_POLICY must be one of keep, stale, delete"),
         }
     }
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+pub mod refresh_lock;
 pub mod repository;
 
 use sqlx::{mysql::MySqlPoolOptions, MySql, Pool};
@@ -0,0 +1,50 @@
+use crate::error::ApiError;
+use sqlx::pool::PoolConnection;
+use sqlx::{MySql, Pool};
+
+const REFRESH_LOCK_NAME: &str = "countries_refresh";
+
+pub struct RefreshLock {
+    conn: PoolConnection<MySql>,
+    released: bool,
+}
+
+impl RefreshLock {
+    pub async fn try_acquire(pool: &Pool<MySql>) -> Result<Option<Self>, ApiError> {
+        let mut conn = pool.acquire().await?;
+
+        let acquired: Option<i64> = sqlx::query_scalar("SELECT GET_LOCK(?, 0)")
+            .bind(REFRESH_LOCK_NAME)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        if acquired != Some(1) {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            conn,
+            released: false,
+        }))
+    }
+
+    pub async fn release(mut self) -> Result<(), ApiError> {
+        sqlx::query("DO RELEASE_LOCK(?)")
+            .bind(REFRESH_LOCK_NAME)
+            .execute(&mut *self.conn)
+            .await?;
+
+        self.released = true;
+        Ok(())
+    }
+}
+
+impl Drop for RefreshLock {
+    fn drop(&mut self) {
+        // GET_LOCK belongs to the connection, so closing it frees the lock
+        // instead of handing a locked connection back to the pool.
+        if !self.released {
+            self.conn.close_on_drop();
+        }
+    }
+}
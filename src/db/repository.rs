@@ -1,87 +1,556 @@
 use crate::error::ApiError;
-use crate::models::{Country, CountryInsert, RefreshMetadata};
-use chrono::Utc;
-use sqlx::{MySql, Transaction};
+use crate::filter_expr::{CompareOp, FilterExpr, FilterValue};
+use crate::models::{Country, CountryCurrency, CountryDiff, CountryField, CountryFilter, CountryPage, CountryHistoryEntry, CountryInsert, CountryOverrides, GdpEstimate, NullsOrder, PageRequest, RefreshDiff, RefreshJob, RefreshJobStatus, RefreshMetadata, RefreshTrigger, SortSpec, UpstreamFetchStats};
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::mysql::{MySqlArguments, MySqlDatabaseError};
+use sqlx::query::QueryAs;
+use sqlx::{FromRow, MySql, Row, Transaction};
+use std::collections::HashMap;
 
 pub async fn find_by_name(
     pool: &sqlx::Pool<MySql>,
     name: &str,
 ) -> Result<Option<Country>, ApiError> {
     let country = sqlx::query_as::<_, Country>(
-        "SELECT id, name, capital, region, population, currency_code, exchange_rate, estimated_gdp, flag_url, last_refreshed_at FROM countries WHERE name = ?"
+        "SELECT id, name, capital, region, population, currency_code, exchange_rate, estimated_gdp, gdp_model, flag_url, last_refreshed_at, stale_since, source FROM countries WHERE name = ?"
     )
     .bind(name)
     .fetch_optional(pool)
     .await?;
 
-    Ok(country)
+    let mut countries: Vec<Country> = country.into_iter().collect();
+    attach_currencies(pool, &mut countries).await?;
+    attach_overrides(pool, &mut countries).await?;
+
+    Ok(countries.pop())
 }
 
-pub async fn find_by_name_case_insensitive(
-    tx: &mut Transaction<'_, MySql>,
-    name: &str,
-) -> Result<Option<Country>, ApiError> {
-    let country = sqlx::query_as::<_, Country>(
-        "SELECT id, name, capital, region, population, currency_code, exchange_rate, estimated_gdp, flag_url, last_refreshed_at FROM countries WHERE LOWER(name) = LOWER(?)"
+// Values bound to dynamically built country queries.
+#[derive(Debug, PartialEq)]
+enum QueryValue {
+    Text(String),
+    Int(i64),
+    Float(f64),
+    DateTime(DateTime<Utc>),
+    Null,
+}
+
+impl QueryValue {
+    fn from_json(value: &serde_json::Value) -> Option<Self> {
+        match value {
+            serde_json::Value::Null => Some(QueryValue::Null),
+            serde_json::Value::String(s) => Some(QueryValue::Text(s.clone())),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Some(QueryValue::Int(i)),
+                None => n.as_f64().map(QueryValue::Float),
+            },
+            _ => None,
+        }
+    }
+}
+
+fn bind_values<'q, O>(
+    mut q: QueryAs<'q, MySql, O, MySqlArguments>,
+    values: &'q [QueryValue],
+) -> QueryAs<'q, MySql, O, MySqlArguments> {
+    for value in values {
+        q = match value {
+            QueryValue::Text(s) => q.bind(s),
+            QueryValue::Int(i) => q.bind(*i),
+            QueryValue::Float(f) => q.bind(*f),
+            QueryValue::DateTime(dt) => q.bind(*dt),
+            QueryValue::Null => q.bind(None::<String>),
+        };
+    }
+    q
+}
+
+fn filter_clause(filter: &CountryFilter) -> (String, Vec<QueryValue>) {
+    let mut conditions = vec!["1=1".to_string()];
+    let mut bindings = Vec::new();
+
+    if !filter.include_stale {
+        conditions.push("stale_since IS NULL".to_string());
+    }
+
+    if !filter.regions.is_empty() {
+        let placeholders = vec!["?"; filter.regions.len()].join(", ");
+        conditions.push(format!("region IN ({})", placeholders));
+        bindings.extend(filter.regions.iter().cloned().map(QueryValue::Text));
+    }
+
+    if let Some(currency) = &filter.currency {
+        conditions.push(currency_exists(Some("= ?")));
+        bindings.push(QueryValue::Text(currency.clone()));
+    }
+
+    match filter.has_currency {
+        Some(true) => conditions.push(currency_exists(None)),
+        Some(false) => conditions.push(format!("NOT {}", currency_exists(None))),
+        None => {}
+    }
+
+    if let Some(min) = filter.min_population {
+        conditions.push("population >= ?".to_string());
+        bindings.push(QueryValue::Int(min));
+    }
+
+    if let Some(max) = filter.max_population {
+        conditions.push("population <= ?".to_string());
+        bindings.push(QueryValue::Int(max));
+    }
+
+    if let Some(min) = filter.min_gdp {
+        conditions.push("estimated_gdp >= ?".to_string());
+        bindings.push(QueryValue::Float(min));
+    }
+
+    if let Some(max) = filter.max_gdp {
+        conditions.push("estimated_gdp <= ?".to_string());
+        bindings.push(QueryValue::Float(max));
+    }
+
+    match filter.capital_missing {
+        Some(true) => conditions.push("(capital IS NULL OR capital = '')".to_string()),
+        Some(false) => conditions.push("(capital IS NOT NULL AND capital <> '')".to_string()),
+        None => {}
+    }
+
+    if let Some(since) = filter.refreshed_since {
+        conditions.push("last_refreshed_at >= ?".to_string());
+        bindings.push(QueryValue::DateTime(since));
+    }
+
+    if let Some(expr) = &filter.expr {
+        conditions.push(expr_clause(expr, &mut bindings));
+    }
+
+    (conditions.join(" AND "), bindings)
+}
+
+fn filter_value(value: &FilterValue) -> QueryValue {
+    match value {
+        FilterValue::Text(s) => QueryValue::Text(s.clone()),
+        FilterValue::Int(i) => QueryValue::Int(*i),
+        FilterValue::Float(f) => QueryValue::Float(*f),
+        FilterValue::DateTime(dt) => QueryValue::DateTime(*dt),
+    }
+}
+
+// Whether the country holds a currency whose code passes `code_test`, or any
+// currency at all without one. Currency filters go through this rather than
+// the primary currency_code column so secondary currencies count too.
+fn currency_exists(code_test: Option<&str>) -> String {
+    match code_test {
+        Some(code_test) => format!(
+            "EXISTS (SELECT 1 FROM country_currencies cc WHERE cc.country_id = countries.id AND cc.code {})",
+            code_test
+        ),
+        None => "EXISTS (SELECT 1 FROM country_currencies cc WHERE cc.country_id = countries.id)".to_string(),
+    }
+}
+
+// Columns come from the `CountryField` whitelist; every literal is bound.
+// `ne` and `eq null` on currency_code negate the whole test, so they mean a
+// country without that currency, or without any.
+fn expr_clause(expr: &FilterExpr, bindings: &mut Vec<QueryValue>) -> String {
+    match expr {
+        FilterExpr::And(lhs, rhs) => {
+            let lhs = expr_clause(lhs, bindings);
+            let rhs = expr_clause(rhs, bindings);
+            format!("({} AND {})", lhs, rhs)
+        }
+        FilterExpr::Or(lhs, rhs) => {
+            let lhs = expr_clause(lhs, bindings);
+            let rhs = expr_clause(rhs, bindings);
+            format!("({} OR {})", lhs, rhs)
+        }
+        FilterExpr::Not(inner) => format!("NOT {}", expr_clause(inner, bindings)),
+        FilterExpr::Compare { field: CountryField::CurrencyCode, op, value } => {
+            bindings.push(filter_value(value));
+            match op {
+                CompareOp::Ne => format!("(NOT {})", currency_exists(Some("= ?"))),
+                op => format!("({})", currency_exists(Some(&format!("{} ?", op.as_sql())))),
+            }
+        }
+        FilterExpr::Compare { field, op, value } => {
+            bindings.push(filter_value(value));
+            format!("({} {} ?)", field.column(), op.as_sql())
+        }
+        FilterExpr::IsNull { field: CountryField::CurrencyCode, negated } => {
+            format!("({}{})", if *negated { "" } else { "NOT " }, currency_exists(None))
+        }
+        FilterExpr::IsNull { field, negated: false } => format!("({} IS NULL)", field.column()),
+        FilterExpr::IsNull { field, negated: true } => format!("({} IS NOT NULL)", field.column()),
+        FilterExpr::In { field, values } => {
+            bindings.extend(values.iter().map(filter_value));
+            let placeholders = vec!["?"; values.len()].join(", ");
+            match field {
+                CountryField::CurrencyCode => {
+                    format!("({})", currency_exists(Some(&format!("IN ({})", placeholders))))
+                }
+                field => format!("({} IN ({}))", field.column(), placeholders),
+            }
+        }
+    }
+}
+
+enum SortSource {
+    NullFlag(CountryField),
+    Field(CountryField),
+    Id,
+}
+
+struct SortKey {
+    expr: String,
+    descending: bool,
+    source: SortSource,
+}
+
+fn sort_nullable(field: CountryField) -> bool {
+    !matches!(
+        field,
+        CountryField::Name | CountryField::Population | CountryField::Source | CountryField::LastRefreshedAt
     )
-    .bind(name)
-    .fetch_optional(&mut **tx)
-    .await?;
+}
 
-    Ok(country)
+fn sort_value(field: CountryField, country: &Country) -> serde_json::Value {
+    match field {
+        CountryField::Name => serde_json::json!(country.name),
+        CountryField::Capital => serde_json::json!(country.capital),
+        CountryField::Region => serde_json::json!(country.region),
+        CountryField::Population => serde_json::json!(country.population),
+        CountryField::CurrencyCode => serde_json::json!(country.currency_code),
+        CountryField::ExchangeRate => serde_json::json!(country.exchange_rate),
+        CountryField::EstimatedGdp => serde_json::json!(country.estimated_gdp),
+        CountryField::GdpPerCapita => serde_json::json!(country
+            .estimated_gdp
+            .filter(|_| country.population != 0)
+            .map(|gdp| gdp / country.population as f64)),
+        CountryField::GdpModel => serde_json::json!(country.gdp_model),
+        CountryField::Source => serde_json::json!(country.source),
+        CountryField::LastRefreshedAt => {
+            serde_json::json!(country.last_refreshed_at.format("%Y-%m-%d %H:%M:%S").to_string())
+        }
+    }
 }
 
-pub async fn find_all(
-    pool: &sqlx::Pool<MySql>,
-    region: Option<String>,
-    currency: Option<String>,
-    sort: Option<String>,
-) -> Result<Vec<Country>, ApiError> {
-    let mut query_parts = vec![
-        "SELECT id, name, capital, region, population, currency_code, exchange_rate, estimated_gdp, flag_url, last_refreshed_at FROM countries WHERE 1=1".to_string()
-    ];
+fn key_value(key: &SortKey, country: &Country) -> serde_json::Value {
+    match key.source {
+        SortSource::NullFlag(field) => serde_json::json!(sort_value(field, country).is_null() as i64),
+        SortSource::Field(field) => sort_value(field, country),
+        SortSource::Id => serde_json::json!(country.id),
+    }
+}
+
+// Every ordering ends on id so that rows never tie, which keyset pagination
+// relies on. Nullable fields get an explicit IS NULL key in front, which both
+// places the NULLs and lets the cursor step across the NULL group.
+fn sort_keys(sort: Option<&SortSpec>) -> Vec<SortKey> {
+    let mut keys = Vec::new();
 
-    let mut bindings: Vec<String> = Vec::new();
+    for &(field, descending) in sort.map(|spec| spec.keys.as_slice()).unwrap_or_default() {
+        if sort_nullable(field) {
+            let nulls_first = match sort.and_then(|spec| spec.nulls) {
+                Some(nulls) => nulls == NullsOrder::First,
+                None => !descending,
+            };
+
+            keys.push(SortKey {
+                expr: format!("({} IS NULL)", field.column()),
+                descending: nulls_first,
+                source: SortSource::NullFlag(field),
+            });
+        }
 
-    if let Some(r) = region {
-        query_parts.push("AND region = ?".to_string());
-        bindings.push(r);
+        keys.push(SortKey {
+            expr: field.column().to_string(),
+            descending,
+            source: SortSource::Field(field),
+        });
     }
 
-    if let Some(c) = currency {
-        query_parts.push("AND currency_code = ?".to_string());
-        bindings.push(c);
+    keys.push(SortKey {
+        expr: "id".to_string(),
+        descending: false,
+        source: SortSource::Id,
+    });
+
+    keys
+}
+
+// `reverse` flips every key, which is how a `prev` cursor walks backwards
+// from its row before the page is put back in sort order.
+fn order_clause(keys: &[SortKey], reverse: bool) -> String {
+    keys.iter()
+        .map(|key| format!("{} {}", key.expr, if key.descending != reverse { "DESC" } else { "ASC" }))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CursorDirection {
+    Next,
+    Prev,
+}
+
+impl CursorDirection {
+    fn as_str(self) -> &'static str {
+        match self {
+            CursorDirection::Next => "next",
+            CursorDirection::Prev => "prev",
+        }
     }
+}
+
+// Rows strictly after the cursor in sort order (or strictly before it for a
+// `prev` cursor): for each key, all earlier keys equal (NULL-safe) and this
+// key past the cursor value.
+fn keyset_clause(
+    keys: &[SortKey],
+    values: &[serde_json::Value],
+    direction: CursorDirection,
+) -> Option<(String, Vec<QueryValue>)> {
+    let mut alternatives = Vec::new();
+    let mut bindings = Vec::new();
 
-    if let Some(sort_param) = sort {
-        match sort_param.as_str() {
-            "gdp_desc" => query_parts.push("ORDER BY estimated_gdp DESC".to_string()),
-            "gdp_asc" => query_parts.push("ORDER BY estimated_gdp ASC".to_string()),
-            "population_desc" => query_parts.push("ORDER BY population DESC".to_string()),
-            "population_asc" => query_parts.push("ORDER BY population ASC".to_string()),
-            _ => {}
+    for (i, key) in keys.iter().enumerate() {
+        let mut terms = Vec::new();
+
+        for (earlier, value) in keys.iter().zip(values).take(i) {
+            terms.push(format!("{} <=> ?", earlier.expr));
+            bindings.push(QueryValue::from_json(value)?);
         }
+
+        let before = key.descending != (direction == CursorDirection::Prev);
+        terms.push(format!("{} {} ?", key.expr, if before { "<" } else { ">" }));
+        bindings.push(QueryValue::from_json(values.get(i)?)?);
+
+        alternatives.push(format!("({})", terms.join(" AND ")));
     }
 
-    let query = query_parts.join(" ");
+    Some((format!("({})", alternatives.join(" OR ")), bindings))
+}
+
+fn encode_cursor(sort: Option<&SortSpec>, direction: CursorDirection, values: Vec<serde_json::Value>) -> String {
+    let json = serde_json::json!({
+        "sort": sort.map(SortSpec::canonical),
+        "dir": direction.as_str(),
+        "values": values,
+    })
+    .to_string();
+    json.bytes().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_cursor(cursor: &str, sort: Option<&SortSpec>) -> Option<(CursorDirection, Vec<serde_json::Value>)> {
+    if !cursor.len().is_multiple_of(2) {
+        return None;
+    }
 
-    let mut q = sqlx::query_as::<_, Country>(&query);
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(cursor.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
 
-    for binding in bindings {
-        q = q.bind(binding);
+    let json: serde_json::Value = serde_json::from_slice(&bytes).ok()?;
+    if json.get("sort")?.as_str() != sort.map(SortSpec::canonical).as_deref() {
+        return None;
     }
 
-    let countries = q.fetch_all(pool).await?;
+    let direction = match json.get("dir")?.as_str()? {
+        "next" => CursorDirection::Next,
+        "prev" => CursorDirection::Prev,
+        _ => return None,
+    };
+
+    Some((direction, json.get("values")?.as_array().cloned()?))
+}
+
+pub async fn find_all(
+    pool: &sqlx::Pool<MySql>,
+    filter: &CountryFilter,
+    sort: Option<&SortSpec>,
+) -> Result<Vec<Country>, ApiError> {
+    let (where_clause, bindings) = filter_clause(filter);
+    let query = format!(
+        "SELECT id, name, capital, region, population, currency_code, exchange_rate, estimated_gdp, gdp_model, flag_url, last_refreshed_at, stale_since, source FROM countries WHERE {} ORDER BY {}",
+        where_clause,
+        order_clause(&sort_keys(sort), false)
+    );
+
+    let mut countries = bind_values(sqlx::query_as::<_, Country>(&query), &bindings)
+        .fetch_all(pool)
+        .await?;
+    attach_currencies(pool, &mut countries).await?;
+    attach_overrides(pool, &mut countries).await?;
 
     Ok(countries)
 }
 
-pub async fn insert(
+// Every stored country, stale ones included, locked until the transaction
+// ends so API writes wait for the refresh that read them.
+pub async fn find_all_for_update(
     tx: &mut Transaction<'_, MySql>,
+) -> Result<Vec<Country>, ApiError> {
+    let mut countries = sqlx::query_as::<_, Country>(
+        "SELECT id, name, capital, region, population, currency_code, exchange_rate, estimated_gdp, gdp_model, flag_url, last_refreshed_at, stale_since, source FROM countries ORDER BY id FOR UPDATE"
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    attach_currencies(&mut **tx, &mut countries).await?;
+
+    Ok(countries)
+}
+
+pub async fn find_page(
+    pool: &sqlx::Pool<MySql>,
+    filter: &CountryFilter,
+    sort: Option<&SortSpec>,
+    page: &PageRequest,
+) -> Result<CountryPage, ApiError> {
+    let (where_clause, mut bindings) = filter_clause(filter);
+
+    let count_query = format!("SELECT COUNT(*) FROM countries WHERE {}", where_clause);
+    let (total,): (i64,) = bind_values(sqlx::query_as(&count_query), &bindings)
+        .fetch_one(pool)
+        .await?;
+
+    let keys = sort_keys(sort);
+    let mut conditions = where_clause;
+    let mut direction = None;
+
+    let (limit, offset) = match page {
+        PageRequest::Offset { limit, offset } => (*limit, *offset),
+        PageRequest::Cursor { limit, cursor } => {
+            if let Some(cursor) = cursor {
+                let (cursor_direction, (clause, cursor_bindings)) = decode_cursor(cursor, sort)
+                    .filter(|(_, values)| values.len() == keys.len())
+                    .and_then(|(dir, values)| Some((dir, keyset_clause(&keys, &values, dir)?)))
+                    .ok_or_else(|| {
+                        let mut errors = HashMap::new();
+                        errors.insert("cursor".to_string(), "is invalid or does not match the sort order".to_string());
+                        ApiError::ValidationError(errors)
+                    })?;
+                conditions = format!("{} AND {}", conditions, clause);
+                bindings.extend(cursor_bindings);
+                direction = Some(cursor_direction);
+            }
+            // One extra row tells whether there is another page beyond this one.
+            (*limit + 1, 0)
+        }
+    };
+
+    bindings.push(QueryValue::Int(limit as i64));
+    bindings.push(QueryValue::Int(offset as i64));
+
+    let query = format!(
+        "SELECT id, name, capital, region, population, currency_code, exchange_rate, estimated_gdp, gdp_model, flag_url, last_refreshed_at, stale_since, source FROM countries WHERE {} ORDER BY {} LIMIT ? OFFSET ?",
+        conditions,
+        order_clause(&keys, direction == Some(CursorDirection::Prev))
+    );
+
+    let mut countries = bind_values(sqlx::query_as::<_, Country>(&query), &bindings)
+        .fetch_all(pool)
+        .await?;
+
+    let mut next_cursor = None;
+    let mut prev_cursor = None;
+    if let PageRequest::Cursor { limit, .. } = page {
+        let more = countries.len() as u64 > *limit;
+        countries.truncate(*limit as usize);
+
+        // A cursor was followed to get here, so there is a page on the side
+        // it came from; the extra row says whether there is one further on.
+        let (has_next, has_prev) = match direction {
+            None => (more, false),
+            Some(CursorDirection::Next) => (more, true),
+            Some(CursorDirection::Prev) => {
+                countries.reverse();
+                (true, more)
+            }
+        };
+
+        let cursor_at = |country: Option<&Country>, direction| {
+            country.map(|country| encode_cursor(sort, direction, keys.iter().map(|key| key_value(key, country)).collect()))
+        };
+        if has_next {
+            next_cursor = cursor_at(countries.last(), CursorDirection::Next);
+        }
+        if has_prev {
+            prev_cursor = cursor_at(countries.first(), CursorDirection::Prev);
+        }
+    }
+
+    attach_currencies(pool, &mut countries).await?;
+    attach_overrides(pool, &mut countries).await?;
+
+    Ok(CountryPage {
+        countries,
+        total,
+        next_cursor,
+        prev_cursor,
+    })
+}
+
+async fn attach_currencies<'c, E>(
+    executor: E,
+    countries: &mut [Country],
+) -> Result<(), ApiError>
+where
+    E: sqlx::Executor<'c, Database = MySql>,
+{
+    if countries.is_empty() {
+        return Ok(());
+    }
+
+    let placeholders = vec!["?"; countries.len()].join(", ");
+    let query = format!(
+        "SELECT country_id, code, name, symbol, is_primary FROM country_currencies WHERE country_id IN ({}) ORDER BY is_primary DESC, id",
+        placeholders
+    );
+
+    let mut q = sqlx::query(&query);
+    for country in countries.iter() {
+        q = q.bind(country.id);
+    }
+
+    let rows = q.fetch_all(executor).await?;
+
+    let mut by_country: HashMap<u64, Vec<CountryCurrency>> = HashMap::new();
+    for row in rows {
+        let country_id: u64 = row.try_get("country_id")?;
+        by_country
+            .entry(country_id)
+            .or_default()
+            .push(CountryCurrency::from_row(&row)?);
+    }
+
+    for country in countries.iter_mut() {
+        country.currencies = by_country.remove(&country.id).unwrap_or_default();
+    }
+
+    Ok(())
+}
+
+fn is_duplicate_key(e: &sqlx::Error) -> bool {
+    const ER_DUP_ENTRY: u16 = 1062;
+
+    match e {
+        sqlx::Error::Database(db) => db
+            .try_downcast_ref::<MySqlDatabaseError>()
+            .is_some_and(|db| db.number() == ER_DUP_ENTRY),
+        _ => false,
+    }
+}
+
+pub async fn create_manual(
+    pool: &sqlx::Pool<MySql>,
     country: &CountryInsert,
-) -> Result<(), ApiError> {
-    sqlx::query(
-        "INSERT INTO countries (name, capital, region, population, currency_code, exchange_rate, estimated_gdp, flag_url, last_refreshed_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+) -> Result<u64, ApiError> {
+    let mut tx = pool.begin().await?;
+
+    let result = sqlx::query(
+        "INSERT INTO countries (name, capital, region, population, currency_code, exchange_rate, estimated_gdp, gdp_model, flag_url, last_refreshed_at, source) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 'manual')"
     )
     .bind(&country.name)
     .bind(&country.capital)
@@ -90,20 +559,36 @@ pub async fn insert(
     .bind(&country.currency_code)
     .bind(country.exchange_rate)
     .bind(country.estimated_gdp)
+    .bind(&country.gdp_model)
     .bind(&country.flag_url)
     .bind(Utc::now())
-    .execute(&mut **tx)
-    .await?;
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        if is_duplicate_key(&e) {
+            ApiError::CountryExists
+        } else {
+            ApiError::from(e)
+        }
+    })?;
 
-    Ok(())
+    let id = result.last_insert_id();
+    replace_currencies_batch(&mut tx, &[(id, country)]).await?;
+
+    tx.commit().await?;
+
+    Ok(id)
 }
 
-pub async fn update(
-    tx: &mut Transaction<'_, MySql>,
+pub async fn update_manual(
+    pool: &sqlx::Pool<MySql>,
+    id: u64,
     country: &CountryInsert,
 ) -> Result<(), ApiError> {
+    let mut tx = pool.begin().await?;
+
     sqlx::query(
-        "UPDATE countries SET capital = ?, region = ?, population = ?, currency_code = ?, exchange_rate = ?, estimated_gdp = ?, flag_url = ?, last_refreshed_at = ? WHERE LOWER(name) = LOWER(?)"
+        "UPDATE countries SET capital = ?, region = ?, population = ?, currency_code = ?, exchange_rate = ?, estimated_gdp = ?, gdp_model = ?, flag_url = ?, last_refreshed_at = ? WHERE id = ?"
     )
     .bind(&country.capital)
     .bind(&country.region)
@@ -111,12 +596,295 @@ pub async fn update(
     .bind(&country.currency_code)
     .bind(country.exchange_rate)
     .bind(country.estimated_gdp)
+    .bind(&country.gdp_model)
     .bind(&country.flag_url)
     .bind(Utc::now())
-    .bind(&country.name)
-    .execute(&mut **tx)
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+
+    replace_currencies_batch(&mut tx, &[(id, country)]).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// Keeps each multi-row statement well below MySQL's placeholder limit.
+pub const UPSERT_CHUNK_SIZE: usize = 100;
+
+// Inserts new countries and updates existing ones (matched on the unique
+// name) with a single statement, so callers should pass at most
+// `UPSERT_CHUNK_SIZE` countries. Returns the row ID of each country, in the
+// order given.
+pub async fn upsert_countries(
+    tx: &mut Transaction<'_, MySql>,
+    countries: &[CountryInsert],
+) -> Result<Vec<u64>, ApiError> {
+    if countries.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let now = Utc::now();
+    let placeholders = vec!["(?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"; countries.len()].join(", ");
+    let query = format!(
+        "INSERT INTO countries (name, capital, region, population, currency_code, exchange_rate, estimated_gdp, gdp_model, flag_url, last_refreshed_at) VALUES {} ON DUPLICATE KEY UPDATE capital = VALUES(capital), region = VALUES(region), population = VALUES(population), currency_code = VALUES(currency_code), exchange_rate = VALUES(exchange_rate), estimated_gdp = VALUES(estimated_gdp), gdp_model = VALUES(gdp_model), flag_url = VALUES(flag_url), last_refreshed_at = VALUES(last_refreshed_at), stale_since = NULL, source = 'upstream'",
+        placeholders
+    );
+
+    let mut q = sqlx::query(&query);
+    for country in countries {
+        q = q
+            .bind(&country.name)
+            .bind(&country.capital)
+            .bind(&country.region)
+            .bind(country.population)
+            .bind(&country.currency_code)
+            .bind(country.exchange_rate)
+            .bind(country.estimated_gdp)
+            .bind(&country.gdp_model)
+            .bind(&country.flag_url)
+            .bind(now);
+    }
+    q.execute(&mut **tx).await?;
+
+    // Look each name up with `name = ?` so MySQL matches it under the same
+    // collation as the unique key did. Names that differ in accents or width
+    // from the stored row still resolve to that row.
+    let query = (0..countries.len())
+        .map(|position| format!("SELECT {} AS position, id FROM countries WHERE name = ?", position))
+        .collect::<Vec<_>>()
+        .join(" UNION ALL ");
+
+    let mut q = sqlx::query_as::<_, (i64, u64)>(&query);
+    for country in countries {
+        q = q.bind(&country.name);
+    }
+
+    let mut ids = vec![None; countries.len()];
+    for (position, id) in q.fetch_all(&mut **tx).await? {
+        ids[position as usize] = Some(id);
+    }
+
+    countries
+        .iter()
+        .zip(ids)
+        .map(|(country, id)| {
+            id.ok_or_else(|| {
+                log::error!("Upserted country {} could not be found again", country.name);
+                ApiError::InternalError
+            })
+        })
+        .collect()
+}
+
+async fn attach_overrides(
+    pool: &sqlx::Pool<MySql>,
+    countries: &mut [Country],
+) -> Result<(), ApiError> {
+    if countries.is_empty() {
+        return Ok(());
+    }
+
+    let placeholders = vec!["?"; countries.len()].join(", ");
+    let query = format!(
+        "SELECT country_name, capital, region, population, flag_url FROM country_overrides WHERE country_name IN ({})",
+        placeholders
+    );
+
+    let mut q = sqlx::query(&query);
+    for country in countries.iter() {
+        q = q.bind(&country.name);
+    }
+
+    let rows = q.fetch_all(pool).await?;
+
+    let mut by_name: HashMap<String, CountryOverrides> = HashMap::new();
+    for row in rows {
+        let country_name: String = row.try_get("country_name")?;
+        by_name.insert(country_name.to_lowercase(), CountryOverrides::from_row(&row)?);
+    }
+
+    for country in countries.iter_mut() {
+        country.overridden_fields = by_name
+            .remove(&country.name.to_lowercase())
+            .map(|overrides| overrides.fields())
+            .unwrap_or_default();
+    }
+
+    Ok(())
+}
+
+pub async fn find_all_overrides(
+    pool: &sqlx::Pool<MySql>,
+) -> Result<HashMap<String, CountryOverrides>, ApiError> {
+    let rows = sqlx::query(
+        "SELECT country_name, capital, region, population, flag_url FROM country_overrides"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    overrides_by_name(rows)
+}
+
+pub async fn find_all_overrides_for_update(
+    tx: &mut Transaction<'_, MySql>,
+) -> Result<HashMap<String, CountryOverrides>, ApiError> {
+    let rows = sqlx::query(
+        "SELECT country_name, capital, region, population, flag_url FROM country_overrides FOR UPDATE"
+    )
+    .fetch_all(&mut **tx)
     .await?;
 
+    overrides_by_name(rows)
+}
+
+fn overrides_by_name(
+    rows: Vec<sqlx::mysql::MySqlRow>,
+) -> Result<HashMap<String, CountryOverrides>, ApiError> {
+    let mut overrides = HashMap::new();
+    for row in rows {
+        let country_name: String = row.try_get("country_name")?;
+        overrides.insert(country_name.to_lowercase(), CountryOverrides::from_row(&row)?);
+    }
+
+    Ok(overrides)
+}
+
+// Replaces the overrides for a country and pins the new values on the stored
+// row straight away, along with `gdp` when the caller re-estimated it. Cleared
+// overrides keep their value until the next refresh.
+pub async fn replace_overrides(
+    pool: &sqlx::Pool<MySql>,
+    country_name: &str,
+    overrides: &CountryOverrides,
+    gdp: Option<&GdpEstimate>,
+) -> Result<(), ApiError> {
+    let mut tx = pool.begin().await?;
+
+    if overrides.is_empty() {
+        sqlx::query("DELETE FROM country_overrides WHERE country_name = ?")
+            .bind(country_name)
+            .execute(&mut *tx)
+            .await?;
+    } else {
+        sqlx::query(
+            "INSERT INTO country_overrides (country_name, capital, region, population, flag_url, updated_at) VALUES (?, ?, ?, ?, ?, ?) ON DUPLICATE KEY UPDATE capital = VALUES(capital), region = VALUES(region), population = VALUES(population), flag_url = VALUES(flag_url), updated_at = VALUES(updated_at)"
+        )
+        .bind(country_name)
+        .bind(&overrides.capital)
+        .bind(&overrides.region)
+        .bind(overrides.population)
+        .bind(&overrides.flag_url)
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "UPDATE countries SET capital = COALESCE(?, capital), region = COALESCE(?, region), population = COALESCE(?, population), flag_url = COALESCE(?, flag_url), last_refreshed_at = last_refreshed_at WHERE name = ?"
+        )
+        .bind(&overrides.capital)
+        .bind(&overrides.region)
+        .bind(overrides.population)
+        .bind(&overrides.flag_url)
+        .bind(country_name)
+        .execute(&mut *tx)
+        .await?;
+
+        if let Some(gdp) = gdp {
+            sqlx::query(
+                "UPDATE countries SET estimated_gdp = ?, gdp_model = ?, last_refreshed_at = last_refreshed_at WHERE name = ?"
+            )
+            .bind(gdp.estimated_gdp)
+            .bind(&gdp.gdp_model)
+            .bind(country_name)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// Like `upsert_countries`, writes its countries with single statements; the
+// refresh passes one chunk at a time.
+pub async fn replace_currencies_batch(
+    tx: &mut Transaction<'_, MySql>,
+    countries: &[(u64, &CountryInsert)],
+) -> Result<(), ApiError> {
+    if countries.is_empty() {
+        return Ok(());
+    }
+
+    let placeholders = vec!["?"; countries.len()].join(", ");
+    let query = format!("DELETE FROM country_currencies WHERE country_id IN ({})", placeholders);
+
+    let mut q = sqlx::query(&query);
+    for (country_id, _) in countries {
+        q = q.bind(country_id);
+    }
+    q.execute(&mut **tx).await?;
+
+    let currencies: Vec<(u64, &CountryCurrency)> = countries
+        .iter()
+        .flat_map(|(country_id, country)| country.currencies.iter().map(move |currency| (*country_id, currency)))
+        .collect();
+
+    if currencies.is_empty() {
+        return Ok(());
+    }
+
+    let placeholders = vec!["(?, ?, ?, ?, ?)"; currencies.len()].join(", ");
+    let query = format!(
+        "INSERT INTO country_currencies (country_id, code, name, symbol, is_primary) VALUES {}",
+        placeholders
+    );
+
+    let mut q = sqlx::query(&query);
+    for (country_id, currency) in currencies {
+        q = q
+            .bind(country_id)
+            .bind(&currency.code)
+            .bind(&currency.name)
+            .bind(&currency.symbol)
+            .bind(currency.is_primary);
+    }
+    q.execute(&mut **tx).await?;
+
+    Ok(())
+}
+
+pub async fn insert_history_batch(
+    tx: &mut Transaction<'_, MySql>,
+    refresh_job_id: u64,
+    countries: &[(u64, &CountryInsert)],
+    recorded_at: DateTime<Utc>,
+) -> Result<(), ApiError> {
+    if countries.is_empty() {
+        return Ok(());
+    }
+
+    let placeholders = vec!["(?, ?, ?, ?, ?, ?, ?)"; countries.len()].join(", ");
+    let query = format!(
+        "INSERT INTO country_history (refresh_job_id, country_id, country_name, population, exchange_rate, estimated_gdp, recorded_at) VALUES {}",
+        placeholders
+    );
+
+    let mut q = sqlx::query(&query);
+    for (country_id, country) in countries {
+        q = q
+            .bind(refresh_job_id)
+            .bind(country_id)
+            .bind(&country.name)
+            .bind(country.population)
+            .bind(country.exchange_rate)
+            .bind(country.estimated_gdp)
+            .bind(recorded_at);
+    }
+    q.execute(&mut **tx).await?;
+
     Ok(())
 }
 
@@ -132,12 +900,69 @@ pub async fn delete(
     Ok(result.rows_affected() > 0)
 }
 
+pub async fn mark_stale(
+    tx: &mut Transaction<'_, MySql>,
+    name: &str,
+    stale_since: DateTime<Utc>,
+) -> Result<(), ApiError> {
+    // Assigning last_refreshed_at to itself stops ON UPDATE CURRENT_TIMESTAMP
+    // from making a stale country look freshly refreshed.
+    sqlx::query("UPDATE countries SET stale_since = COALESCE(stale_since, ?), last_refreshed_at = last_refreshed_at WHERE name = ?")
+        .bind(stale_since)
+        .bind(name)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn delete_in_tx(
+    tx: &mut Transaction<'_, MySql>,
+    name: &str,
+) -> Result<(), ApiError> {
+    sqlx::query("DELETE FROM countries WHERE name = ?")
+        .bind(name)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn find_history(
+    pool: &sqlx::Pool<MySql>,
+    name: &str,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<CountryHistoryEntry>, ApiError> {
+    let mut query = "SELECT refresh_job_id, recorded_at, population, exchange_rate, estimated_gdp FROM country_history WHERE country_name = ?".to_string();
+
+    if from.is_some() {
+        query.push_str(" AND recorded_at >= ?");
+    }
+    if to.is_some() {
+        query.push_str(" AND recorded_at <= ?");
+    }
+    query.push_str(" ORDER BY recorded_at ASC, id ASC");
+
+    let mut q = sqlx::query_as::<_, CountryHistoryEntry>(&query).bind(name);
+    if let Some(from) = from {
+        q = q.bind(from);
+    }
+    if let Some(to) = to {
+        q = q.bind(to);
+    }
+
+    let history = q.fetch_all(pool).await?;
+
+    Ok(history)
+}
+
 pub async fn get_top_by_gdp(
     pool: &sqlx::Pool<MySql>,
     limit: i32,
 ) -> Result<Vec<Country>, ApiError> {
     let countries = sqlx::query_as::<_, Country>(
-        "SELECT id, name, capital, region, population, currency_code, exchange_rate, estimated_gdp, flag_url, last_refreshed_at FROM countries WHERE estimated_gdp IS NOT NULL ORDER BY estimated_gdp DESC LIMIT ?"
+        "SELECT id, name, capital, region, population, currency_code, exchange_rate, estimated_gdp, gdp_model, flag_url, last_refreshed_at, stale_since, source FROM countries WHERE estimated_gdp IS NOT NULL AND stale_since IS NULL ORDER BY estimated_gdp DESC LIMIT ?"
     )
     .bind(limit)
     .fetch_all(pool)
@@ -149,12 +974,16 @@ pub async fn get_top_by_gdp(
 pub async fn update_metadata(
     tx: &mut Transaction<'_, MySql>,
     total_countries: i32,
+    rate_provider: &str,
+    rates_as_of: DateTime<Utc>,
 ) -> Result<(), ApiError> {
     sqlx::query(
-        "UPDATE refresh_metadata SET total_countries = ?, last_refreshed_at = ? WHERE id = 1"
+        "UPDATE refresh_metadata SET total_countries = ?, last_refreshed_at = ?, rate_provider = ?, rates_as_of = ? WHERE id = 1"
     )
     .bind(total_countries)
     .bind(Utc::now())
+    .bind(rate_provider)
+    .bind(rates_as_of)
     .execute(&mut **tx)
     .await?;
 
@@ -165,10 +994,515 @@ pub async fn get_metadata(
     pool: &sqlx::Pool<MySql>,
 ) -> Result<RefreshMetadata, ApiError> {
     let metadata = sqlx::query_as::<_, RefreshMetadata>(
-        "SELECT total_countries, last_refreshed_at FROM refresh_metadata WHERE id = 1"
+        "SELECT total_countries, last_refreshed_at, rate_provider, rates_as_of FROM refresh_metadata WHERE id = 1"
     )
     .fetch_one(pool)
     .await?;
 
     Ok(metadata)
-}
\ No newline at end of file
+}
+
+pub async fn store_exchange_rates(
+    tx: &mut Transaction<'_, MySql>,
+    rate_date: NaiveDate,
+    rates: &HashMap<String, f64>,
+    provider: &str,
+    fetched_at: DateTime<Utc>,
+) -> Result<(), ApiError> {
+    let rates: Vec<(&String, &f64)> = rates.iter().collect();
+
+    for chunk in rates.chunks(UPSERT_CHUNK_SIZE) {
+        let placeholders = vec!["(?, ?, ?, ?, ?)"; chunk.len()].join(", ");
+        let query = format!(
+            "INSERT INTO exchange_rates (rate_date, currency_code, rate, provider, fetched_at) VALUES {} ON DUPLICATE KEY UPDATE rate = VALUES(rate), provider = VALUES(provider), fetched_at = VALUES(fetched_at)",
+            placeholders
+        );
+
+        let mut q = sqlx::query(&query);
+        for (currency_code, rate) in chunk {
+            q = q
+                .bind(rate_date)
+                .bind(*currency_code)
+                .bind(**rate)
+                .bind(provider)
+                .bind(fetched_at);
+        }
+        q.execute(&mut **tx).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn find_latest_exchange_rates(
+    pool: &sqlx::Pool<MySql>,
+) -> Result<Option<(NaiveDate, HashMap<String, f64>)>, ApiError> {
+    let rate_date: Option<NaiveDate> = sqlx::query_scalar("SELECT MAX(rate_date) FROM exchange_rates")
+        .fetch_one(pool)
+        .await?;
+
+    let Some(rate_date) = rate_date else {
+        return Ok(None);
+    };
+
+    let rows: Vec<(String, f64)> = sqlx::query_as(
+        "SELECT currency_code, rate FROM exchange_rates WHERE rate_date = ?"
+    )
+    .bind(rate_date)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Some((rate_date, rows.into_iter().collect())))
+}
+
+// The most recent stored per-USD rate for a currency.
+pub async fn find_latest_rate(
+    pool: &sqlx::Pool<MySql>,
+    currency_code: &str,
+) -> Result<Option<f64>, ApiError> {
+    let rate = sqlx::query_scalar(
+        "SELECT rate FROM exchange_rates WHERE currency_code = ? ORDER BY rate_date DESC LIMIT 1"
+    )
+    .bind(currency_code)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(rate)
+}
+
+// Finds the most recent date on or before `date` that has a rate for both
+// currencies, and returns both per-USD rates from that same table.
+pub async fn find_conversion_rates(
+    pool: &sqlx::Pool<MySql>,
+    from: &str,
+    to: &str,
+    date: NaiveDate,
+) -> Result<Option<(NaiveDate, f64, f64)>, ApiError> {
+    let row: Option<(NaiveDate, f64, f64)> = sqlx::query_as(
+        "SELECT f.rate_date, f.rate, t.rate FROM exchange_rates f INNER JOIN exchange_rates t ON t.rate_date = f.rate_date AND t.currency_code = ? WHERE f.currency_code = ? AND f.rate_date <= ? ORDER BY f.rate_date DESC LIMIT 1"
+    )
+    .bind(to)
+    .bind(from)
+    .bind(date)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row)
+}
+
+pub async fn create_refresh_job(
+    pool: &sqlx::Pool<MySql>,
+    triggered_by: RefreshTrigger,
+    replay_of: Option<u64>,
+) -> Result<u64, ApiError> {
+    let result = sqlx::query(
+        "INSERT INTO refresh_jobs (status, triggered_by, replay_of, created_at) VALUES (?, ?, ?, ?)"
+    )
+    .bind(RefreshJobStatus::Queued.as_str())
+    .bind(triggered_by.as_str())
+    .bind(replay_of)
+    .bind(Utc::now())
+    .execute(pool)
+    .await?;
+
+    Ok(result.last_insert_id())
+}
+
+pub async fn find_refresh_job(
+    pool: &sqlx::Pool<MySql>,
+    id: u64,
+) -> Result<Option<RefreshJob>, ApiError> {
+    let job = sqlx::query_as::<_, RefreshJob>(
+        "SELECT id, status, triggered_by, replay_of, total_countries, countries_processed, rate_provider, rates_as_of, upstream_stats, reconcile_policy, reconciled_countries, error, created_at, started_at, finished_at FROM refresh_jobs WHERE id = ?"
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(job)
+}
+
+pub async fn update_refresh_job_status(
+    pool: &sqlx::Pool<MySql>,
+    id: u64,
+    status: RefreshJobStatus,
+) -> Result<(), ApiError> {
+    sqlx::query(
+        "UPDATE refresh_jobs SET status = ?, started_at = COALESCE(started_at, ?) WHERE id = ?"
+    )
+    .bind(status.as_str())
+    .bind(Utc::now())
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn update_refresh_job_progress(
+    pool: &sqlx::Pool<MySql>,
+    id: u64,
+    total_countries: i32,
+    countries_processed: i32,
+) -> Result<(), ApiError> {
+    sqlx::query(
+        "UPDATE refresh_jobs SET total_countries = ?, countries_processed = ? WHERE id = ?"
+    )
+    .bind(total_countries)
+    .bind(countries_processed)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn update_refresh_job_rates(
+    pool: &sqlx::Pool<MySql>,
+    id: u64,
+    rate_provider: &str,
+    rates_as_of: DateTime<Utc>,
+) -> Result<(), ApiError> {
+    sqlx::query(
+        "UPDATE refresh_jobs SET rate_provider = ?, rates_as_of = ? WHERE id = ?"
+    )
+    .bind(rate_provider)
+    .bind(rates_as_of)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn update_refresh_job_upstream_stats(
+    pool: &sqlx::Pool<MySql>,
+    id: u64,
+    stats: &[UpstreamFetchStats],
+) -> Result<(), ApiError> {
+    let stats = serde_json::to_string(stats).map_err(|_| ApiError::InternalError)?;
+
+    sqlx::query(
+        "UPDATE refresh_jobs SET upstream_stats = ? WHERE id = ?"
+    )
+    .bind(stats)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn update_refresh_job_reconciliation(
+    pool: &sqlx::Pool<MySql>,
+    id: u64,
+    policy: &str,
+    countries: &[String],
+) -> Result<(), ApiError> {
+    let countries = serde_json::to_string(countries).map_err(|_| ApiError::InternalError)?;
+
+    sqlx::query(
+        "UPDATE refresh_jobs SET reconcile_policy = ?, reconciled_countries = ? WHERE id = ?"
+    )
+    .bind(policy)
+    .bind(countries)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn insert_refresh_changes(
+    tx: &mut Transaction<'_, MySql>,
+    refresh_job_id: u64,
+    diff: &RefreshDiff,
+) -> Result<(), ApiError> {
+    let mut rows: Vec<(&str, &str, Option<String>)> = Vec::new();
+
+    for name in &diff.added {
+        rows.push((name, "added", None));
+    }
+    for name in &diff.removed {
+        rows.push((name, "removed", None));
+    }
+    for country in &diff.changed {
+        let changes = serde_json::to_string(&country.changes).map_err(|_| ApiError::InternalError)?;
+        rows.push((&country.name, "changed", Some(changes)));
+    }
+
+    for chunk in rows.chunks(UPSERT_CHUNK_SIZE) {
+        let placeholders = vec!["(?, ?, ?, ?)"; chunk.len()].join(", ");
+        let query = format!(
+            "INSERT INTO refresh_changes (refresh_job_id, country_name, change_type, changes) VALUES {}",
+            placeholders
+        );
+
+        let mut q = sqlx::query(&query);
+        for (country_name, change_type, changes) in chunk {
+            q = q
+                .bind(refresh_job_id)
+                .bind(*country_name)
+                .bind(*change_type)
+                .bind(changes.as_deref());
+        }
+        q.execute(&mut **tx).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn find_refresh_changes(
+    pool: &sqlx::Pool<MySql>,
+    refresh_job_id: u64,
+    reconcile_policy: &str,
+) -> Result<RefreshDiff, ApiError> {
+    let rows: Vec<(String, String, Option<String>)> = sqlx::query_as(
+        "SELECT country_name, change_type, changes FROM refresh_changes WHERE refresh_job_id = ? ORDER BY id"
+    )
+    .bind(refresh_job_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut diff = RefreshDiff {
+        reconcile_policy: reconcile_policy.to_string(),
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+    };
+
+    for (name, change_type, changes) in rows {
+        match change_type.as_str() {
+            "added" => diff.added.push(name),
+            "removed" => diff.removed.push(name),
+            _ => {
+                let changes = match changes {
+                    Some(json) => serde_json::from_str(&json).map_err(|_| ApiError::InternalError)?,
+                    None => Default::default(),
+                };
+                diff.changed.push(CountryDiff { name, changes });
+            }
+        }
+    }
+
+    Ok(diff)
+}
+
+pub async fn finish_refresh_job(
+    pool: &sqlx::Pool<MySql>,
+    id: u64,
+    status: RefreshJobStatus,
+    error: Option<&str>,
+) -> Result<(), ApiError> {
+    sqlx::query(
+        "UPDATE refresh_jobs SET status = ?, error = ?, finished_at = ? WHERE id = ?"
+    )
+    .bind(status.as_str())
+    .bind(error)
+    .bind(Utc::now())
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn find_latest_refresh_job(
+    pool: &sqlx::Pool<MySql>,
+    triggered_by: RefreshTrigger,
+) -> Result<Option<RefreshJob>, ApiError> {
+    let job = sqlx::query_as::<_, RefreshJob>(
+        "SELECT id, status, triggered_by, replay_of, total_countries, countries_processed, rate_provider, rates_as_of, upstream_stats, reconcile_policy, reconciled_countries, error, created_at, started_at, finished_at FROM refresh_jobs WHERE triggered_by = ? ORDER BY id DESC LIMIT 1"
+    )
+    .bind(triggered_by.as_str())
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(job)
+}
+
+pub async fn find_active_refresh_job(
+    pool: &sqlx::Pool<MySql>,
+) -> Result<Option<RefreshJob>, ApiError> {
+    let job = sqlx::query_as::<_, RefreshJob>(
+        "SELECT id, status, triggered_by, replay_of, total_countries, countries_processed, rate_provider, rates_as_of, upstream_stats, reconcile_policy, reconciled_countries, error, created_at, started_at, finished_at FROM refresh_jobs WHERE status IN (?, ?, ?, ?) ORDER BY id DESC LIMIT 1"
+    )
+    .bind(RefreshJobStatus::Queued.as_str())
+    .bind(RefreshJobStatus::Fetching.as_str())
+    .bind(RefreshJobStatus::Writing.as_str())
+    .bind(RefreshJobStatus::Rendering.as_str())
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(job)
+}
+
+pub async fn fail_interrupted_refresh_jobs(
+    pool: &sqlx::Pool<MySql>,
+) -> Result<u64, ApiError> {
+    let result = sqlx::query(
+        "UPDATE refresh_jobs SET status = ?, error = ?, finished_at = ? WHERE status NOT IN (?, ?, ?)"
+    )
+    .bind(RefreshJobStatus::Failed.as_str())
+    .bind("Interrupted by server restart")
+    .bind(Utc::now())
+    .bind(RefreshJobStatus::Done.as_str())
+    .bind(RefreshJobStatus::Failed.as_str())
+    .bind(RefreshJobStatus::Skipped.as_str())
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn sort_keys_put_a_null_flag_before_nullable_fields_and_end_on_id() {
+        let sort = SortSpec::parse("-estimated_gdp,name").unwrap();
+        let keys = sort_keys(Some(&sort));
+
+        assert_eq!(
+            order_clause(&keys, false),
+            "(estimated_gdp IS NULL) ASC, estimated_gdp DESC, name ASC, id ASC"
+        );
+        assert_eq!(
+            order_clause(&keys, true),
+            "(estimated_gdp IS NULL) DESC, estimated_gdp ASC, name DESC, id DESC"
+        );
+    }
+
+    fn where_clause(filter: &str) -> (String, Vec<QueryValue>) {
+        let mut bindings = Vec::new();
+        let clause = expr_clause(&FilterExpr::parse(filter).unwrap(), &mut bindings);
+        (clause, bindings)
+    }
+
+    #[test]
+    fn filters_on_plain_columns_compare_the_column() {
+        assert_eq!(
+            where_clause("population gt 5 and region in ('Asia', 'Africa')"),
+            (
+                "((population > ?) AND (region IN (?, ?)))".to_string(),
+                vec![
+                    QueryValue::Int(5),
+                    QueryValue::Text("Asia".to_string()),
+                    QueryValue::Text("Africa".to_string()),
+                ]
+            )
+        );
+        assert_eq!(where_clause("capital eq null").0, "(capital IS NULL)");
+    }
+
+    #[test]
+    fn currency_filters_match_any_held_currency() {
+        let exists = "EXISTS (SELECT 1 FROM country_currencies cc WHERE cc.country_id = countries.id";
+
+        assert_eq!(
+            where_clause("currency_code eq 'EUR'"),
+            (format!("({} AND cc.code = ?))", exists), vec![QueryValue::Text("EUR".to_string())])
+        );
+        assert_eq!(where_clause("currency_code ne 'EUR'").0, format!("(NOT {} AND cc.code = ?))", exists));
+        assert_eq!(where_clause("currency_code ge 'M'").0, format!("({} AND cc.code >= ?))", exists));
+        assert_eq!(
+            where_clause("currency_code in ('EUR', 'USD')").0,
+            format!("({} AND cc.code IN (?, ?)))", exists)
+        );
+        assert_eq!(where_clause("currency_code eq null").0, format!("(NOT {}))", exists));
+        assert_eq!(where_clause("currency_code ne null").0, format!("({}))", exists));
+
+        // The same test as `currency=EUR`.
+        let filter = CountryFilter {
+            currency: Some("EUR".to_string()),
+            include_stale: true,
+            ..Default::default()
+        };
+        assert_eq!(filter_clause(&filter).0, format!("1=1 AND {} AND cc.code = ?)", exists));
+    }
+
+    #[test]
+    fn keyset_clause_steps_past_the_cursor_row() {
+        let sort = SortSpec::parse("-population").unwrap();
+        let keys = sort_keys(Some(&sort));
+
+        let (clause, bindings) = keyset_clause(&keys, &[json!(1000), json!(7)], CursorDirection::Next).unwrap();
+
+        assert_eq!(clause, "((population < ?) OR (population <=> ? AND id > ?))");
+        assert_eq!(bindings, vec![QueryValue::Int(1000), QueryValue::Int(1000), QueryValue::Int(7)]);
+    }
+
+    #[test]
+    fn keyset_clause_reverses_comparisons_for_prev_cursors() {
+        let sort = SortSpec::parse("-population").unwrap();
+        let keys = sort_keys(Some(&sort));
+
+        let (clause, _) = keyset_clause(&keys, &[json!(1000), json!(7)], CursorDirection::Prev).unwrap();
+
+        assert_eq!(clause, "((population > ?) OR (population <=> ? AND id < ?))");
+    }
+
+    #[test]
+    fn keyset_clause_binds_nulls_and_rejects_short_or_unbindable_values() {
+        let sort = SortSpec::parse("capital").unwrap();
+        let keys = sort_keys(Some(&sort));
+
+        let (_, bindings) = keyset_clause(&keys, &[json!(1), json!(null), json!(3)], CursorDirection::Next).unwrap();
+        assert_eq!(
+            bindings,
+            vec![
+                QueryValue::Int(1),
+                QueryValue::Int(1),
+                QueryValue::Null,
+                QueryValue::Int(1),
+                QueryValue::Null,
+                QueryValue::Int(3),
+            ]
+        );
+
+        assert!(keyset_clause(&keys, &[json!(1), json!(null)], CursorDirection::Next).is_none());
+        assert!(keyset_clause(&keys, &[json!(1), json!([]), json!(3)], CursorDirection::Next).is_none());
+    }
+
+    #[test]
+    fn cursors_round_trip_with_their_direction() {
+        let sort = SortSpec::parse("name").unwrap();
+        let values = vec![json!("Ghana"), json!(12)];
+
+        let next = encode_cursor(Some(&sort), CursorDirection::Next, values.clone());
+        let prev = encode_cursor(Some(&sort), CursorDirection::Prev, values.clone());
+
+        assert!(next.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(decode_cursor(&next, Some(&sort)), Some((CursorDirection::Next, values.clone())));
+        assert_eq!(decode_cursor(&prev, Some(&sort)), Some((CursorDirection::Prev, values)));
+    }
+
+    #[test]
+    fn cursors_are_tied_to_their_sort_order() {
+        let sort = SortSpec::parse("name").unwrap();
+        let other = SortSpec::parse("-name").unwrap();
+        let cursor = encode_cursor(Some(&sort), CursorDirection::Next, vec![json!(1)]);
+
+        assert!(decode_cursor(&cursor, Some(&other)).is_none());
+        assert!(decode_cursor(&cursor, None).is_none());
+        assert!(decode_cursor(&encode_cursor(None, CursorDirection::Next, vec![json!(1)]), None).is_some());
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        let hex = |json: &str| json.bytes().map(|b| format!("{:02x}", b)).collect::<String>();
+
+        assert!(decode_cursor("abc", None).is_none());
+        assert!(decode_cursor("zz", None).is_none());
+        assert!(decode_cursor(&hex("not json"), None).is_none());
+        assert!(decode_cursor(&hex(r#"{"sort":null,"dir":"sideways","values":[]}"#), None).is_none());
+        assert!(decode_cursor(&hex(r#"{"sort":null,"values":[1]}"#), None).is_none());
+        assert_eq!(
+            decode_cursor(&hex(r#"{"sort":null,"dir":"next","values":[1]}"#), None),
+            Some((CursorDirection::Next, vec![json!(1)]))
+        );
+    }
+}
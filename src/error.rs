@@ -1,4 +1,5 @@
-use actix_web::{error::ResponseError, http::StatusCode, HttpResponse};
+use actix_web::{error::ResponseError, http::{header, StatusCode}, HttpResponse};
+use chrono::{DateTime, Utc};
 use serde::Serialize;
 use std::collections::HashMap;
 use thiserror::Error;
@@ -8,11 +9,38 @@ pub enum ApiError {
     #[error("Country not found")]
     NotFound,
     
+    #[error("Country already exists")]
+    CountryExists,
+    
+    #[error("Country is maintained by the upstream refresh")]
+    UpstreamCountry(String),
+    
+    #[error("Refresh job not found")]
+    RefreshJobNotFound,
+    
+    #[error("Snapshot not found")]
+    SnapshotNotFound,
+    
+    #[error("Exchange rate not found")]
+    RateNotFound,
+    
+    #[error("Refresh already in progress")]
+    RefreshInProgress {
+        job_id: Option<u64>,
+        started_at: Option<DateTime<Utc>>,
+    },
+    
     #[error("Validation failed")]
     ValidationError(HashMap<String, String>),
     
     #[error("External data source unavailable")]
-    ExternalApiError(String),
+    ExternalApi(String),
+    
+    #[error("External data source temporarily disabled")]
+    CircuitOpen {
+        upstream: String,
+        retry_after_secs: u64,
+    },
     
     #[error("Database error")]
     DatabaseError(#[from] sqlx::Error),
@@ -21,6 +49,21 @@ pub enum ApiError {
     InternalError,
 }
 
+impl ApiError {
+    pub fn detail(&self) -> String {
+        match self {
+            ApiError::ExternalApi(api_name) => {
+                format!("{}: could not fetch data from {}", self, api_name)
+            }
+            ApiError::CircuitOpen { upstream, retry_after_secs } => {
+                format!("{}: {} failing, retry in {} seconds", self, upstream, retry_after_secs)
+            }
+            ApiError::DatabaseError(e) => format!("{}: {}", self, e),
+            _ => self.to_string(),
+        }
+    }
+}
+
 #[derive(Serialize)]
 struct ErrorResponse {
     error: String,
@@ -32,8 +75,15 @@ impl ResponseError for ApiError {
     fn status_code(&self) -> StatusCode {
         match self {
             ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::CountryExists => StatusCode::CONFLICT,
+            ApiError::UpstreamCountry(_) => StatusCode::CONFLICT,
+            ApiError::RefreshJobNotFound => StatusCode::NOT_FOUND,
+            ApiError::SnapshotNotFound => StatusCode::NOT_FOUND,
+            ApiError::RateNotFound => StatusCode::NOT_FOUND,
+            ApiError::RefreshInProgress { .. } => StatusCode::CONFLICT,
             ApiError::ValidationError(_) => StatusCode::BAD_REQUEST,
-            ApiError::ExternalApiError(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::ExternalApi(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::CircuitOpen { .. } => StatusCode::SERVICE_UNAVAILABLE,
             ApiError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
         }
@@ -49,18 +99,71 @@ impl ResponseError for ApiError {
                     details: None,
                 })
             }
+            ApiError::CountryExists => {
+                HttpResponse::Conflict().json(ErrorResponse {
+                    error: "Country already exists".to_string(),
+                    details: None,
+                })
+            }
+            ApiError::UpstreamCountry(name) => {
+                HttpResponse::Conflict().json(ErrorResponse {
+                    error: "Country is maintained by the upstream refresh".to_string(),
+                    details: Some(serde_json::Value::String(format!(
+                        "Edits would be undone by the next refresh, pin fields with PUT /countries/{}/overrides instead",
+                        name
+                    ))),
+                })
+            }
+            ApiError::RefreshJobNotFound => {
+                HttpResponse::NotFound().json(ErrorResponse {
+                    error: "Refresh job not found".to_string(),
+                    details: None,
+                })
+            }
+            ApiError::SnapshotNotFound => {
+                HttpResponse::NotFound().json(ErrorResponse {
+                    error: "Snapshot not found".to_string(),
+                    details: None,
+                })
+            }
+            ApiError::RateNotFound => {
+                HttpResponse::NotFound().json(ErrorResponse {
+                    error: "Exchange rate not found".to_string(),
+                    details: None,
+                })
+            }
+            ApiError::RefreshInProgress { job_id, started_at } => {
+                HttpResponse::Conflict().json(ErrorResponse {
+                    error: "Refresh already in progress".to_string(),
+                    details: Some(serde_json::json!({
+                        "job_id": job_id,
+                        "started_at": started_at.map(|dt| dt.to_rfc3339()),
+                    })),
+                })
+            }
             ApiError::ValidationError(details) => {
                 HttpResponse::BadRequest().json(ErrorResponse {
                     error: "Validation failed".to_string(),
                     details: Some(serde_json::to_value(details).unwrap()),
                 })
             }
-            ApiError::ExternalApiError(api_name) => {
+            ApiError::ExternalApi(api_name) => {
                 HttpResponse::ServiceUnavailable().json(ErrorResponse {
                     error: "External data source unavailable".to_string(),
                     details: Some(serde_json::Value::String(format!("Could not fetch data from {}", api_name))),
                 })
             }
+            ApiError::CircuitOpen { upstream, retry_after_secs } => {
+                HttpResponse::ServiceUnavailable()
+                    .insert_header((header::RETRY_AFTER, retry_after_secs.to_string()))
+                    .json(ErrorResponse {
+                        error: "External data source temporarily disabled".to_string(),
+                        details: Some(serde_json::Value::String(format!(
+                            "Too many failures from {}, retry in {} seconds",
+                            upstream, retry_after_secs
+                        ))),
+                    })
+            }
             ApiError::DatabaseError(_) => {
                 HttpResponse::InternalServerError().json(ErrorResponse {
                     error: "Internal server error".to_string(),
@@ -79,7 +182,7 @@ impl ResponseError for ApiError {
 
 impl From<reqwest::Error> for ApiError {
     fn from(e: reqwest::Error) -> Self {
-        ApiError::ExternalApiError(format!("External API: {}", e))
+        ApiError::ExternalApi(format!("External API: {}", e))
     }
 }
 
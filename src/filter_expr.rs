@@ -0,0 +1,802 @@
+use crate::models::CountryField;
+use crate::utils::parse_datetime_param;
+use chrono::{DateTime, Utc};
+
+// Parser for the `filter=` query parameter on GET /countries, e.g.
+//
+//     population gt 10000000 and (region eq 'Asia' or currency_code in ('EUR','USD'))
+//
+// Comparisons are `eq ne gt ge lt le` and `in (...)`, combined with `and`,
+// `or`, `not` and parentheses. Strings are single-quoted with `''` as the
+// escape, and `eq null` / `ne null` test for missing values. A quoted plain
+// date compared with last_refreshed_at means the whole of that day, so
+// `eq '2024-01-31'` matches any time on the 31st. Only the fields in
+// `CountryField::FILTERABLE` can be referenced; the repository turns the
+// parsed expression into a parameterised WHERE clause. currency_code tests
+// every currency a country holds, like the `currency=` parameter.
+
+const MAX_DEPTH: usize = 32;
+const MAX_LENGTH: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldKind {
+    Text,
+    Integer,
+    Number,
+    DateTime,
+}
+
+fn field_kind(field: CountryField) -> FieldKind {
+    match field {
+        CountryField::Population => FieldKind::Integer,
+        CountryField::ExchangeRate | CountryField::EstimatedGdp | CountryField::GdpPerCapita => FieldKind::Number,
+        CountryField::LastRefreshedAt => FieldKind::DateTime,
+        _ => FieldKind::Text,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl CompareOp {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "eq" => Some(CompareOp::Eq),
+            "ne" => Some(CompareOp::Ne),
+            "gt" => Some(CompareOp::Gt),
+            "ge" => Some(CompareOp::Ge),
+            "lt" => Some(CompareOp::Lt),
+            "le" => Some(CompareOp::Le),
+            _ => None,
+        }
+    }
+
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            CompareOp::Eq => "=",
+            CompareOp::Ne => "<>",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterValue {
+    Text(String),
+    Int(i64),
+    Float(f64),
+    DateTime(DateTime<Utc>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterExpr {
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+    Not(Box<FilterExpr>),
+    Compare {
+        field: CountryField,
+        op: CompareOp,
+        value: FilterValue,
+    },
+    IsNull {
+        field: CountryField,
+        negated: bool,
+    },
+    In {
+        field: CountryField,
+        values: Vec<FilterValue>,
+    },
+}
+
+impl FilterExpr {
+    // Errors name the 1-based character position of the offending token.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        if input.chars().count() > MAX_LENGTH {
+            return Err(format!("must be at most {} characters", MAX_LENGTH));
+        }
+
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0, depth: 0 };
+        let expr = parser.parse_or()?;
+
+        match parser.peek() {
+            Token { kind: TokenKind::End, .. } => Ok(expr),
+            token => Err(token.unexpected("'and', 'or' or end of filter")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word(String),
+    Text(String),
+    Number(String),
+    LParen,
+    RParen,
+    Comma,
+    End,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match &self.kind {
+            TokenKind::Word(word) => format!("'{}'", word),
+            TokenKind::Text(text) => format!("string '{}'", text),
+            TokenKind::Number(number) => format!("number {}", number),
+            TokenKind::LParen => "'('".to_string(),
+            TokenKind::RParen => "')'".to_string(),
+            TokenKind::Comma => "','".to_string(),
+            TokenKind::End => "end of filter".to_string(),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> String {
+        format!(
+            "unexpected {} at position {}, expected {}",
+            self.describe(),
+            self.position,
+            expected
+        )
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.kind, TokenKind::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let position = i + 1;
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let kind = match c {
+            '(' => {
+                i += 1;
+                TokenKind::LParen
+            }
+            ')' => {
+                i += 1;
+                TokenKind::RParen
+            }
+            ',' => {
+                i += 1;
+                TokenKind::Comma
+            }
+            '\'' => {
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        Some('\'') if chars.get(i + 1) == Some(&'\'') => {
+                            text.push('\'');
+                            i += 2;
+                        }
+                        Some('\'') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&c) => {
+                            text.push(c);
+                            i += 1;
+                        }
+                        None => return Err(format!("unterminated string starting at position {}", position)),
+                    }
+                }
+                TokenKind::Text(text)
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                // An exponent such as `1e12` or `2.5E-3` belongs to the number.
+                if matches!(chars.get(i), Some('e' | 'E')) {
+                    let digits = match chars.get(i + 1) {
+                        Some('+' | '-') => i + 2,
+                        _ => i + 1,
+                    };
+                    if chars.get(digits).is_some_and(char::is_ascii_digit) {
+                        i = digits;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                TokenKind::Number(chars[start..i].iter().collect())
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                TokenKind::Word(chars[start..i].iter().collect())
+            }
+            other => return Err(format!("unexpected character '{}' at position {}", other, position)),
+        };
+
+        tokens.push(Token { kind, position });
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::End,
+        position: chars.len() + 1,
+    });
+
+    Ok(tokens)
+}
+
+// The right-hand side of a comparison. A plain date on a timestamp field
+// stands for every second of that day rather than a single value.
+enum Operand {
+    Value(FilterValue),
+    Day {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    },
+}
+
+impl Operand {
+    fn compare(self, field: CountryField, op: CompareOp) -> FilterExpr {
+        let compare = |op, at| FilterExpr::Compare {
+            field,
+            op,
+            value: FilterValue::DateTime(at),
+        };
+
+        match self {
+            Operand::Value(value) => FilterExpr::Compare { field, op, value },
+            Operand::Day { start, end } => match op {
+                CompareOp::Eq => FilterExpr::And(
+                    Box::new(compare(CompareOp::Ge, start)),
+                    Box::new(compare(CompareOp::Le, end)),
+                ),
+                CompareOp::Ne => FilterExpr::Or(
+                    Box::new(compare(CompareOp::Lt, start)),
+                    Box::new(compare(CompareOp::Gt, end)),
+                ),
+                CompareOp::Gt => compare(CompareOp::Gt, end),
+                CompareOp::Ge => compare(CompareOp::Ge, start),
+                CompareOp::Lt => compare(CompareOp::Lt, start),
+                CompareOp::Le => compare(CompareOp::Le, end),
+            },
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.kind != TokenKind::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<FilterExpr, String> {
+        let mut expr = self.parse_and()?;
+        while self.peek().is_keyword("or") {
+            self.next();
+            let rhs = self.parse_and()?;
+            expr = FilterExpr::Or(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<FilterExpr, String> {
+        let mut expr = self.parse_unary()?;
+        while self.peek().is_keyword("and") {
+            self.next();
+            let rhs = self.parse_unary()?;
+            expr = FilterExpr::And(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<FilterExpr, String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(format!(
+                "nesting deeper than {} levels at position {}",
+                MAX_DEPTH,
+                self.peek().position
+            ));
+        }
+
+        let expr = if self.peek().is_keyword("not") {
+            self.next();
+            FilterExpr::Not(Box::new(self.parse_unary()?))
+        } else if self.peek().kind == TokenKind::LParen {
+            self.next();
+            let expr = self.parse_or()?;
+            let token = self.next();
+            if token.kind != TokenKind::RParen {
+                return Err(token.unexpected("')'"));
+            }
+            expr
+        } else {
+            self.parse_comparison()?
+        };
+
+        self.depth -= 1;
+        Ok(expr)
+    }
+
+    fn parse_comparison(&mut self) -> Result<FilterExpr, String> {
+        let token = self.next();
+        let field = match &token.kind {
+            TokenKind::Word(word) => CountryField::parse_among(word, &CountryField::FILTERABLE).ok_or_else(|| {
+                format!(
+                    "unknown field '{}' at position {}, expected one of {}",
+                    word,
+                    token.position,
+                    CountryField::list(&CountryField::FILTERABLE)
+                )
+            })?,
+            _ => return Err(token.unexpected("a field name, 'not' or '('")),
+        };
+
+        let token = self.next();
+        if token.is_keyword("in") {
+            return self.parse_in_list(field);
+        }
+
+        let op = match &token.kind {
+            TokenKind::Word(word) => CompareOp::parse(&word.to_ascii_lowercase()),
+            _ => None,
+        }
+        .ok_or_else(|| token.unexpected("one of eq, ne, gt, ge, lt, le, in"))?;
+
+        if self.peek().is_keyword("null") {
+            let token = self.next();
+            return match op {
+                CompareOp::Eq => Ok(FilterExpr::IsNull { field, negated: false }),
+                CompareOp::Ne => Ok(FilterExpr::IsNull { field, negated: true }),
+                _ => Err(format!(
+                    "null at position {} can only be compared with eq or ne",
+                    token.position
+                )),
+            };
+        }
+
+        Ok(self.parse_operand(field)?.compare(field, op))
+    }
+
+    fn parse_in_list(&mut self, field: CountryField) -> Result<FilterExpr, String> {
+        let token = self.next();
+        if token.kind != TokenKind::LParen {
+            return Err(token.unexpected("'('"));
+        }
+
+        let mut operands = vec![self.parse_operand(field)?];
+        loop {
+            let token = self.next();
+            match token.kind {
+                TokenKind::Comma => operands.push(self.parse_operand(field)?),
+                TokenKind::RParen => break,
+                _ => return Err(token.unexpected("',' or ')'")),
+            }
+        }
+
+        // Whole days cannot go in an IN list, so a list holding any plain
+        // date becomes an `or` of one `eq` per entry instead.
+        if operands.iter().any(|operand| matches!(operand, Operand::Day { .. })) {
+            let mut operands = operands.into_iter().map(|operand| operand.compare(field, CompareOp::Eq));
+            let first = operands.next().expect("an in list has at least one value");
+            return Ok(operands.fold(first, |expr, rhs| FilterExpr::Or(Box::new(expr), Box::new(rhs))));
+        }
+
+        let values = operands
+            .into_iter()
+            .filter_map(|operand| match operand {
+                Operand::Value(value) => Some(value),
+                Operand::Day { .. } => None,
+            })
+            .collect();
+
+        Ok(FilterExpr::In { field, values })
+    }
+
+    fn parse_operand(&mut self, field: CountryField) -> Result<Operand, String> {
+        let token = self.next();
+        let mismatch = |expected: &str| {
+            format!(
+                "{} at position {} is not valid for {}, expected {}",
+                token.describe(),
+                token.position,
+                field.as_str(),
+                expected
+            )
+        };
+
+        match (field_kind(field), &token.kind) {
+            (FieldKind::Text, TokenKind::Text(text)) => Ok(Operand::Value(FilterValue::Text(text.clone()))),
+            (FieldKind::Text, TokenKind::Number(_)) => Err(mismatch("a quoted string")),
+            // `1e9` is accepted as long as it names a whole number.
+            (FieldKind::Integer, TokenKind::Number(number)) => number
+                .parse::<i64>()
+                .ok()
+                .or_else(|| {
+                    number
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|value| value.fract() == 0.0 && value.abs() < i64::MAX as f64)
+                        .map(|value| value as i64)
+                })
+                .map(|value| Operand::Value(FilterValue::Int(value)))
+                .ok_or_else(|| mismatch("an integer")),
+            (FieldKind::Integer, TokenKind::Text(_)) => Err(mismatch("an integer")),
+            (FieldKind::Number, TokenKind::Number(number)) => number
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .map(|value| Operand::Value(FilterValue::Float(value)))
+                .ok_or_else(|| mismatch("a number")),
+            (FieldKind::Number, TokenKind::Text(_)) => Err(mismatch("a number")),
+            // Only a plain date resolves differently at the start and the end of the day.
+            (FieldKind::DateTime, TokenKind::Text(text)) => {
+                match (parse_datetime_param(text, false), parse_datetime_param(text, true)) {
+                    (Some(start), Some(end)) if start != end => Ok(Operand::Day { start, end }),
+                    (Some(at), _) => Ok(Operand::Value(FilterValue::DateTime(at))),
+                    _ => Err(mismatch("a quoted date (YYYY-MM-DD) or RFC 3339 timestamp")),
+                }
+            }
+            (FieldKind::DateTime, TokenKind::Number(_)) => {
+                Err(mismatch("a quoted date (YYYY-MM-DD) or RFC 3339 timestamp"))
+            }
+            _ => Err(token.unexpected("a value")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn cmp(field: CountryField, op: CompareOp, value: FilterValue) -> FilterExpr {
+        FilterExpr::Compare { field, op, value }
+    }
+
+    fn text(value: &str) -> FilterValue {
+        FilterValue::Text(value.to_string())
+    }
+
+    fn at(day: u32, hour: u32, minute: u32, second: u32) -> FilterValue {
+        FilterValue::DateTime(Utc.with_ymd_and_hms(2024, 1, day, hour, minute, second).unwrap())
+    }
+
+    fn and(lhs: FilterExpr, rhs: FilterExpr) -> FilterExpr {
+        FilterExpr::And(Box::new(lhs), Box::new(rhs))
+    }
+
+    fn or(lhs: FilterExpr, rhs: FilterExpr) -> FilterExpr {
+        FilterExpr::Or(Box::new(lhs), Box::new(rhs))
+    }
+
+    fn not(inner: FilterExpr) -> FilterExpr {
+        FilterExpr::Not(Box::new(inner))
+    }
+
+    fn parse(input: &str) -> FilterExpr {
+        FilterExpr::parse(input).unwrap()
+    }
+
+    fn error(input: &str) -> String {
+        FilterExpr::parse(input).unwrap_err()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            parse("name eq 'a' or region eq 'b' and population gt 1"),
+            or(
+                cmp(CountryField::Name, CompareOp::Eq, text("a")),
+                and(
+                    cmp(CountryField::Region, CompareOp::Eq, text("b")),
+                    cmp(CountryField::Population, CompareOp::Gt, FilterValue::Int(1)),
+                ),
+            )
+        );
+    }
+
+    #[test]
+    fn parentheses_override_precedence_and_keywords_ignore_case() {
+        assert_eq!(
+            parse("(name EQ 'a' OR region eq 'b') And population LE 1"),
+            and(
+                or(
+                    cmp(CountryField::Name, CompareOp::Eq, text("a")),
+                    cmp(CountryField::Region, CompareOp::Eq, text("b")),
+                ),
+                cmp(CountryField::Population, CompareOp::Le, FilterValue::Int(1)),
+            )
+        );
+    }
+
+    #[test]
+    fn doubled_quotes_escape_a_quote() {
+        assert_eq!(
+            parse("name eq 'Côte d''Ivoire'"),
+            cmp(CountryField::Name, CompareOp::Eq, text("Côte d'Ivoire"))
+        );
+        assert_eq!(parse("name eq ''''"), cmp(CountryField::Name, CompareOp::Eq, text("'")));
+        assert_eq!(parse("name eq ''"), cmp(CountryField::Name, CompareOp::Eq, text("")));
+        assert_eq!(error("name eq 'abc"), "unterminated string starting at position 9");
+        assert_eq!(error("name eq 'it''s"), "unterminated string starting at position 9");
+    }
+
+    #[test]
+    fn negative_and_decimal_numbers() {
+        assert_eq!(
+            parse("exchange_rate ge -1.5"),
+            cmp(CountryField::ExchangeRate, CompareOp::Ge, FilterValue::Float(-1.5))
+        );
+        assert_eq!(
+            parse("estimated_gdp lt .5"),
+            cmp(CountryField::EstimatedGdp, CompareOp::Lt, FilterValue::Float(0.5))
+        );
+        assert_eq!(
+            parse("population gt -3"),
+            cmp(CountryField::Population, CompareOp::Gt, FilterValue::Int(-3))
+        );
+        assert_eq!(
+            error("population gt 1.5"),
+            "number 1.5 at position 15 is not valid for population, expected an integer"
+        );
+    }
+
+    #[test]
+    fn exponents_are_part_of_the_number() {
+        assert_eq!(
+            parse("estimated_gdp gt 1e12"),
+            cmp(CountryField::EstimatedGdp, CompareOp::Gt, FilterValue::Float(1e12))
+        );
+        assert_eq!(
+            parse("exchange_rate lt 2.5E-3"),
+            cmp(CountryField::ExchangeRate, CompareOp::Lt, FilterValue::Float(0.0025))
+        );
+        assert_eq!(
+            parse("population ge 1e9"),
+            cmp(CountryField::Population, CompareOp::Ge, FilterValue::Int(1_000_000_000))
+        );
+        assert_eq!(
+            error("population ge 1.5e0"),
+            "number 1.5e0 at position 15 is not valid for population, expected an integer"
+        );
+        assert_eq!(
+            error("exchange_rate gt 1e999"),
+            "number 1e999 at position 18 is not valid for exchange_rate, expected a number"
+        );
+        // Without digits after it the `e` is not an exponent.
+        assert_eq!(
+            error("estimated_gdp gt 1e"),
+            "unexpected 'e' at position 19, expected 'and', 'or' or end of filter"
+        );
+    }
+
+    #[test]
+    fn integers_outside_i64_are_rejected() {
+        assert_eq!(
+            error("population gt 9223372036854775808"),
+            "number 9223372036854775808 at position 15 is not valid for population, expected an integer"
+        );
+    }
+
+    #[test]
+    fn not_nests() {
+        assert_eq!(
+            parse("not not name eq 'a'"),
+            not(not(cmp(CountryField::Name, CompareOp::Eq, text("a"))))
+        );
+        assert_eq!(
+            parse("not (population gt 1 and not region eq 'x')"),
+            not(and(
+                cmp(CountryField::Population, CompareOp::Gt, FilterValue::Int(1)),
+                not(cmp(CountryField::Region, CompareOp::Eq, text("x"))),
+            ))
+        );
+    }
+
+    #[test]
+    fn nesting_is_limited_to_max_depth() {
+        let nots = |n: usize| format!("{}name eq 'a'", "not ".repeat(n));
+        assert!(FilterExpr::parse(&nots(MAX_DEPTH - 1)).is_ok());
+        assert_eq!(
+            error(&nots(MAX_DEPTH)),
+            format!("nesting deeper than {} levels at position {}", MAX_DEPTH, MAX_DEPTH * 4 + 1)
+        );
+
+        let parens = |n: usize| format!("{}name eq 'a'{}", "(".repeat(n), ")".repeat(n));
+        assert!(FilterExpr::parse(&parens(MAX_DEPTH - 1)).is_ok());
+        assert_eq!(
+            error(&parens(MAX_DEPTH)),
+            format!("nesting deeper than {} levels at position {}", MAX_DEPTH, MAX_DEPTH + 1)
+        );
+    }
+
+    #[test]
+    fn in_lists() {
+        assert_eq!(
+            parse("currency_code in ('EUR', 'USD')"),
+            FilterExpr::In {
+                field: CountryField::CurrencyCode,
+                values: vec![text("EUR"), text("USD")],
+            }
+        );
+        assert_eq!(
+            parse("population IN (1,2e3)"),
+            FilterExpr::In {
+                field: CountryField::Population,
+                values: vec![FilterValue::Int(1), FilterValue::Int(2000)],
+            }
+        );
+        assert_eq!(error("currency_code in ()"), "unexpected ')' at position 19, expected a value");
+        assert_eq!(
+            error("currency_code in ('EUR' 'USD')"),
+            "unexpected string 'USD' at position 25, expected ',' or ')'"
+        );
+        assert_eq!(
+            error("currency_code in 'EUR'"),
+            "unexpected string 'EUR' at position 18, expected '('"
+        );
+        assert_eq!(
+            error("currency_code in ('EUR', 1)"),
+            "number 1 at position 26 is not valid for currency_code, expected a quoted string"
+        );
+    }
+
+    #[test]
+    fn eq_and_ne_null_test_for_missing_values() {
+        assert_eq!(
+            parse("capital eq null"),
+            FilterExpr::IsNull {
+                field: CountryField::Capital,
+                negated: false,
+            }
+        );
+        assert_eq!(
+            parse("capital ne NULL"),
+            FilterExpr::IsNull {
+                field: CountryField::Capital,
+                negated: true,
+            }
+        );
+        assert_eq!(error("capital gt null"), "null at position 12 can only be compared with eq or ne");
+        assert_eq!(
+            parse("capital eq 'null'"),
+            cmp(CountryField::Capital, CompareOp::Eq, text("null"))
+        );
+    }
+
+    #[test]
+    fn plain_dates_cover_the_whole_day() {
+        let field = CountryField::LastRefreshedAt;
+        let start = || at(31, 0, 0, 0);
+        let end = || at(31, 23, 59, 59);
+
+        assert_eq!(
+            parse("last_refreshed_at eq '2024-01-31'"),
+            and(cmp(field, CompareOp::Ge, start()), cmp(field, CompareOp::Le, end()))
+        );
+        assert_eq!(
+            parse("last_refreshed_at ne '2024-01-31'"),
+            or(cmp(field, CompareOp::Lt, start()), cmp(field, CompareOp::Gt, end()))
+        );
+        assert_eq!(parse("last_refreshed_at gt '2024-01-31'"), cmp(field, CompareOp::Gt, end()));
+        assert_eq!(parse("last_refreshed_at ge '2024-01-31'"), cmp(field, CompareOp::Ge, start()));
+        assert_eq!(parse("last_refreshed_at lt '2024-01-31'"), cmp(field, CompareOp::Lt, start()));
+        assert_eq!(parse("last_refreshed_at le '2024-01-31'"), cmp(field, CompareOp::Le, end()));
+    }
+
+    #[test]
+    fn timestamps_compare_exactly() {
+        let field = CountryField::LastRefreshedAt;
+
+        assert_eq!(
+            parse("last_refreshed_at eq '2024-01-31T12:00:00+02:00'"),
+            cmp(field, CompareOp::Eq, at(31, 10, 0, 0))
+        );
+        assert_eq!(
+            parse("last_refreshed_at in ('2024-01-01T00:00:00Z', '2024-01-02T00:00:00Z')"),
+            FilterExpr::In {
+                field,
+                values: vec![at(1, 0, 0, 0), at(2, 0, 0, 0)],
+            }
+        );
+    }
+
+    #[test]
+    fn in_lists_with_plain_dates_match_each_day() {
+        let field = CountryField::LastRefreshedAt;
+
+        assert_eq!(
+            parse("last_refreshed_at in ('2024-01-01', '2024-01-02T06:00:00Z', '2024-01-03')"),
+            or(
+                or(
+                    and(cmp(field, CompareOp::Ge, at(1, 0, 0, 0)), cmp(field, CompareOp::Le, at(1, 23, 59, 59))),
+                    cmp(field, CompareOp::Eq, at(2, 6, 0, 0)),
+                ),
+                and(cmp(field, CompareOp::Ge, at(3, 0, 0, 0)), cmp(field, CompareOp::Le, at(3, 23, 59, 59))),
+            )
+        );
+    }
+
+    #[test]
+    fn errors_name_the_offending_token_and_position() {
+        assert_eq!(
+            error("colour eq 'red'"),
+            "unknown field 'colour' at position 1, expected one of name, capital, region, population, currency_code, exchange_rate, estimated_gdp, gdp_model, source, last_refreshed_at"
+        );
+        assert_eq!(
+            error("name like 'a'"),
+            "unexpected 'like' at position 6, expected one of eq, ne, gt, ge, lt, le, in"
+        );
+        assert_eq!(
+            error("name eq 5"),
+            "number 5 at position 9 is not valid for name, expected a quoted string"
+        );
+        assert_eq!(
+            error("population eq '5'"),
+            "string '5' at position 15 is not valid for population, expected an integer"
+        );
+        assert_eq!(
+            error("last_refreshed_at gt 'soon'"),
+            "string 'soon' at position 22 is not valid for last_refreshed_at, expected a quoted date (YYYY-MM-DD) or RFC 3339 timestamp"
+        );
+        assert_eq!(error("name eq 'a' & region eq 'b'"), "unexpected character '&' at position 13");
+        assert_eq!(error("(name eq 'a'"), "unexpected end of filter at position 13, expected ')'");
+        assert_eq!(
+            error("name eq 'a' region"),
+            "unexpected 'region' at position 13, expected 'and', 'or' or end of filter"
+        );
+        assert_eq!(
+            error(") name eq 'a'"),
+            "unexpected ')' at position 1, expected a field name, 'not' or '('"
+        );
+        assert_eq!(error("name eq"), "unexpected end of filter at position 8, expected a value");
+    }
+
+    #[test]
+    fn long_filters_are_rejected_before_parsing() {
+        assert_eq!(
+            error(&"a".repeat(MAX_LENGTH + 1)),
+            format!("must be at most {} characters", MAX_LENGTH)
+        );
+    }
+}
@@ -1,23 +1,133 @@
 use crate::config::Config;
 use crate::db::{repository, DbPool};
 use crate::error::ApiError;
-use crate::services::CountryService;
-use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use crate::filter_expr::FilterExpr;
+use crate::models::{
+    CountryCurrency, CountryFilter, CountryHistoryEntry, CountryInsert, CountryOverrides, CountryPayload,
+    NullsOrder, PageRequest, RefreshJob, RefreshMetadata, RefreshTrigger, SortSpec,
+};
+use crate::services::country_service::MANUAL_SOURCE;
+use crate::services::snapshot_archive::SnapshotKind;
+use crate::services::{CountryService, RefreshSource, SchedulerState};
+use crate::utils::parse_datetime_param;
+use actix_web::{delete, get, http::header, patch, post, put, web, HttpRequest, HttpResponse, Responder};
+use chrono::{NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
 use std::path::PathBuf;
 
+const MAX_PAGE_SIZE: u64 = 100;
+// A request without a page size gets the largest page, so a client unaware of
+// pagination still sees as many countries as it can, plus the Link header.
+const DEFAULT_PAGE_SIZE: u64 = MAX_PAGE_SIZE;
+const PAGINATION_PARAMS: [&str; 5] = ["page", "per_page", "limit", "offset", "cursor"];
+
+// gdp_model of a GDP figure entered through the API rather than estimated.
+const MANUAL_GDP_MODEL: &str = "manual";
+
+// Pagination takes one of three forms: `page`/`per_page`, `limit` with
+// `offset`, or `limit` with an optional `cursor` for keyset pagination, where
+// the next and previous cursors are handed out in the Link header. Without any
+// of these parameters the first page of `DEFAULT_PAGE_SIZE` countries is
+// returned.
+//
+// Every parameter arrives as a string and is checked by `parse_country_query`,
+// so that each bad parameter gets its own message instead of the first serde
+// error winning.
 #[derive(Deserialize)]
 pub struct CountryQuery {
     region: Option<String>,
     currency: Option<String>,
+    has_currency: Option<String>,
+    min_population: Option<String>,
+    max_population: Option<String>,
+    min_gdp: Option<String>,
+    max_gdp: Option<String>,
+    capital_missing: Option<String>,
+    refreshed_since: Option<String>,
+    filter: Option<String>,
     sort: Option<String>,
+    nulls: Option<String>,
+    include_stale: Option<String>,
+    page: Option<String>,
+    per_page: Option<String>,
+    limit: Option<String>,
+    offset: Option<String>,
+    cursor: Option<String>,
+    #[serde(flatten)]
+    unknown: HashMap<String, String>,
+}
+
+struct CountryListParams {
+    filter: CountryFilter,
+    sort: Option<SortSpec>,
+    style: PageStyle,
+    cursor: Option<String>,
+}
+
+enum PageStyle {
+    Page { page: u64, per_page: u64, offset: u64 },
+    Offset { limit: u64, offset: u64 },
+    Cursor { limit: u64 },
+}
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ConvertQuery {
+    from: Option<String>,
+    to: Option<String>,
+    amount: Option<String>,
+    date: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RefreshQuery {
+    replay_job: Option<u64>,
+    dry_run: Option<bool>,
 }
 
 #[derive(Serialize)]
 pub struct RefreshResponse {
     message: String,
-    total_countries: i32,
-    last_refreshed_at: String,
+    job_id: u64,
+    status_url: String,
+}
+
+#[derive(Serialize)]
+pub struct SchedulerStatus {
+    enabled: bool,
+    next_run_at: Option<String>,
+    previous_run: Option<RefreshJob>,
+}
+
+#[derive(Serialize)]
+pub struct StatusResponse {
+    #[serde(flatten)]
+    metadata: RefreshMetadata,
+    rates_age_secs: Option<i64>,
+    scheduler: SchedulerStatus,
+}
+
+#[derive(Serialize)]
+pub struct HistoryResponse {
+    name: String,
+    history: Vec<CountryHistoryEntry>,
+}
+
+#[derive(Serialize)]
+pub struct ConvertResponse {
+    from: String,
+    to: String,
+    amount: f64,
+    rate: f64,
+    result: f64,
+    rate_date: NaiveDate,
 }
 
 #[derive(Serialize)]
@@ -28,33 +138,654 @@ pub struct DeleteResponse {
 #[post("/countries/refresh")]
 async fn refresh_countries(
     pool: web::Data<DbPool>,
-    config: web::Data<Config>,
+    service: web::Data<CountryService>,
+    query: web::Query<RefreshQuery>,
 ) -> Result<impl Responder, ApiError> {
-    let service = CountryService::new(config.external_api_timeout_secs);
-    
-    let (total_countries, last_refreshed_at) = service.refresh_countries(&pool).await?;
-
-    Ok(HttpResponse::Ok().json(RefreshResponse {
-        message: format!("Successfully refreshed {} countries", total_countries),
-        total_countries,
-        last_refreshed_at: last_refreshed_at.to_rfc3339(),
-    }))
+    let source = match query.replay_job {
+        Some(snapshot_job_id) => {
+            if !service.snapshots().exists(snapshot_job_id).await {
+                return Err(ApiError::SnapshotNotFound);
+            }
+            RefreshSource::Snapshot(snapshot_job_id)
+        }
+        None => RefreshSource::Upstream,
+    };
+
+    if query.dry_run.unwrap_or(false) {
+        let diff = service.preview_refresh(&pool, source).await?;
+        return Ok(HttpResponse::Ok().json(diff));
+    }
+
+    let lock = CountryService::acquire_refresh_lock(&pool).await?;
+    let service = service.into_inner();
+    let job_id = repository::create_refresh_job(&pool, RefreshTrigger::Manual, query.replay_job).await?;
+
+    let pool = pool.get_ref().clone();
+    tokio::spawn(async move {
+        service.run_refresh_job(&pool, job_id, lock, source).await;
+    });
+
+    let status_url = format!("/refresh-jobs/{}", job_id);
+
+    Ok(HttpResponse::Accepted()
+        .insert_header((header::LOCATION, status_url.clone()))
+        .json(RefreshResponse {
+            message: "Refresh started".to_string(),
+            job_id,
+            status_url,
+        }))
+}
+
+#[get("/refresh-jobs/{id}")]
+async fn get_refresh_job(
+    pool: web::Data<DbPool>,
+    id: web::Path<u64>,
+) -> Result<impl Responder, ApiError> {
+    let job = repository::find_refresh_job(&pool, id.into_inner())
+        .await?
+        .ok_or(ApiError::RefreshJobNotFound)?;
+
+    Ok(HttpResponse::Ok().json(job))
+}
+
+#[get("/refreshes/{id}/changes")]
+async fn get_refresh_changes(
+    pool: web::Data<DbPool>,
+    id: web::Path<u64>,
+) -> Result<impl Responder, ApiError> {
+    let job = repository::find_refresh_job(&pool, id.into_inner())
+        .await?
+        .ok_or(ApiError::RefreshJobNotFound)?;
+
+    let policy = job.reconcile_policy.as_deref().unwrap_or("keep");
+    let changes = repository::find_refresh_changes(&pool, job.id, policy).await?;
+
+    Ok(HttpResponse::Ok().json(changes))
+}
+
+#[get("/refresh-jobs/{id}/snapshots/{kind}")]
+async fn get_refresh_snapshot(
+    service: web::Data<CountryService>,
+    path: web::Path<(u64, String)>,
+) -> Result<impl Responder, ApiError> {
+    let (job_id, kind) = path.into_inner();
+    let kind = SnapshotKind::parse(&kind).ok_or(ApiError::SnapshotNotFound)?;
+
+    let bytes = service
+        .snapshots()
+        .read_compressed(job_id, kind)
+        .await
+        .ok_or(ApiError::SnapshotNotFound)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/gzip")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"refresh-{}-{}\"", job_id, kind.file_name()),
+        ))
+        .body(bytes))
 }
 
 #[get("/countries")]
 async fn get_countries(
+    req: HttpRequest,
     pool: web::Data<DbPool>,
     query: web::Query<CountryQuery>,
 ) -> Result<impl Responder, ApiError> {
-    let countries = repository::find_all(
-        &pool,
-        query.region.clone(),
-        query.currency.clone(),
-        query.sort.clone(),
-    )
-    .await?;
+    let CountryListParams {
+        filter,
+        sort,
+        style,
+        cursor,
+    } = parse_country_query(query.into_inner())?;
+
+    let page = match style {
+        PageStyle::Page { per_page, offset, .. } => PageRequest::Offset {
+            limit: per_page,
+            offset,
+        },
+        PageStyle::Offset { limit, offset } => PageRequest::Offset { limit, offset },
+        PageStyle::Cursor { limit } => PageRequest::Cursor { limit, cursor },
+    };
+
+    let result = repository::find_page(&pool, &filter, sort.as_ref(), &page).await?;
+    let total = result.total.max(0) as u64;
+
+    let mut links = Vec::new();
+    match style {
+        PageStyle::Page { page, per_page, .. } => {
+            let last = total.div_ceil(per_page).max(1);
+            let link = |page: u64| vec![("page", page.to_string()), ("per_page", per_page.to_string())];
+
+            links.push(page_link(&req, &link(1), "first"));
+            if page > 1 {
+                links.push(page_link(&req, &link((page - 1).min(last)), "prev"));
+            }
+            if page < last {
+                links.push(page_link(&req, &link(page + 1), "next"));
+            }
+            links.push(page_link(&req, &link(last), "last"));
+        }
+        PageStyle::Offset { limit, offset } => {
+            let last = total.saturating_sub(1) / limit * limit;
+            let link = |offset: u64| vec![("limit", limit.to_string()), ("offset", offset.to_string())];
+
+            links.push(page_link(&req, &link(0), "first"));
+            if offset > 0 {
+                links.push(page_link(&req, &link(offset.saturating_sub(limit).min(last)), "prev"));
+            }
+            if let Some(next) = offset.checked_add(limit).filter(|&next| next < total) {
+                links.push(page_link(&req, &link(next), "next"));
+            }
+            links.push(page_link(&req, &link(last), "last"));
+        }
+        PageStyle::Cursor { limit } => {
+            links.push(page_link(&req, &[("limit", limit.to_string())], "first"));
+            if let Some(cursor) = &result.prev_cursor {
+                links.push(page_link(
+                    &req,
+                    &[("limit", limit.to_string()), ("cursor", cursor.clone())],
+                    "prev",
+                ));
+            }
+            if let Some(cursor) = &result.next_cursor {
+                links.push(page_link(
+                    &req,
+                    &[("limit", limit.to_string()), ("cursor", cursor.clone())],
+                    "next",
+                ));
+            }
+        }
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header(("X-Total-Count", total.to_string()))
+        .insert_header((header::LINK, links.join(", ")))
+        .json(result.countries))
+}
+
+fn parse_country_query(query: CountryQuery) -> Result<CountryListParams, ApiError> {
+    let mut errors = HashMap::new();
+
+    for key in query.unknown.keys() {
+        errors.insert(key.clone(), "is not a recognised query parameter".to_string());
+    }
+
+    // `region=Africa,Europe` matches any of the listed regions.
+    let regions: Vec<String> = non_empty_param("region", query.region, &mut errors)
+        .map(|regions| {
+            let regions: Vec<String> = regions.split(',').map(|region| region.trim().to_string()).collect();
+            if regions.iter().any(String::is_empty) {
+                errors.insert("region".to_string(), "must be a comma-separated list of regions".to_string());
+            }
+            regions
+        })
+        .unwrap_or_default();
+
+    let currency = non_empty_param("currency", query.currency, &mut errors).and_then(|code| {
+        if code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+            Some(code.to_uppercase())
+        } else {
+            errors.insert("currency".to_string(), "must be a 3-letter currency code".to_string());
+            None
+        }
+    });
+
+    let sort_given = query.sort.is_some();
+    let mut sort = non_empty_param("sort", query.sort, &mut errors).and_then(|sort| {
+        SortSpec::parse(&sort)
+            .map_err(|message| errors.insert("sort".to_string(), message))
+            .ok()
+    });
+
+    if let Some(nulls) = non_empty_param("nulls", query.nulls, &mut errors) {
+        match (NullsOrder::parse(&nulls), sort.as_mut()) {
+            (Some(nulls), Some(sort)) => sort.nulls = Some(nulls),
+            (Some(_), None) if !sort_given => {
+                errors.insert("nulls".to_string(), "requires sort".to_string());
+            }
+            (Some(_), None) => {}
+            (None, _) => {
+                errors.insert("nulls".to_string(), "must be first or last".to_string());
+            }
+        }
+    }
+
+    let has_currency = bool_param("has_currency", query.has_currency, &mut errors);
+    let capital_missing = bool_param("capital_missing", query.capital_missing, &mut errors);
+    let include_stale = bool_param("include_stale", query.include_stale, &mut errors).unwrap_or(false);
+
+    let min_population = parsed_param::<i64>("min_population", query.min_population, &mut errors, "must be an integer");
+    let max_population = parsed_param::<i64>("max_population", query.max_population, &mut errors, "must be an integer");
+    if let (Some(min), Some(max)) = (min_population, max_population) {
+        if min > max {
+            errors.insert("max_population".to_string(), "must not be less than min_population".to_string());
+        }
+    }
+
+    let min_gdp = float_param("min_gdp", query.min_gdp, &mut errors);
+    let max_gdp = float_param("max_gdp", query.max_gdp, &mut errors);
+    if let (Some(min), Some(max)) = (min_gdp, max_gdp) {
+        if min > max {
+            errors.insert("max_gdp".to_string(), "must not be less than min_gdp".to_string());
+        }
+    }
+
+    let refreshed_since = non_empty_param("refreshed_since", query.refreshed_since, &mut errors).and_then(|value| {
+        let parsed = parse_datetime_param(&value, false);
+        if parsed.is_none() {
+            errors.insert(
+                "refreshed_since".to_string(),
+                "must be a date (YYYY-MM-DD) or RFC 3339 timestamp".to_string(),
+            );
+        }
+        parsed
+    });
+
+    let expr = non_empty_param("filter", query.filter, &mut errors).and_then(|filter| {
+        FilterExpr::parse(&filter)
+            .map_err(|message| errors.insert("filter".to_string(), message))
+            .ok()
+    });
+
+    let page = number_param("page", query.page, &mut errors);
+    let per_page = number_param("per_page", query.per_page, &mut errors);
+    let limit = number_param("limit", query.limit, &mut errors);
+    let offset = number_param("offset", query.offset, &mut errors);
+    let cursor = non_empty_param("cursor", query.cursor, &mut errors);
+
+    let uses_page = page.is_some() || per_page.is_some();
+    let uses_limit = limit.is_some() || offset.is_some() || cursor.is_some();
+
+    if uses_page && uses_limit {
+        errors.insert("page".to_string(), "cannot be combined with limit, offset or cursor".to_string());
+    }
+    if cursor.is_some() && offset.is_some() {
+        errors.insert("cursor".to_string(), "cannot be combined with offset".to_string());
+    }
+    if page == Some(0) {
+        errors.insert("page".to_string(), "must be at least 1".to_string());
+    }
+
+    let (size_field, size) = match limit {
+        Some(limit) => ("limit", limit),
+        None => ("per_page", per_page.unwrap_or(DEFAULT_PAGE_SIZE)),
+    };
+    if size == 0 || size > MAX_PAGE_SIZE {
+        errors.insert(size_field.to_string(), format!("must be between 1 and {}", MAX_PAGE_SIZE));
+    }
+
+    // Offsets are bound as signed integers, so anything past i64::MAX (or a
+    // page whose offset overflows) is rejected rather than wrapped.
+    let page_offset = page
+        .unwrap_or(1)
+        .checked_sub(1)
+        .and_then(|skipped| skipped.checked_mul(size))
+        .filter(|&offset| i64::try_from(offset).is_ok());
+    if uses_page && page != Some(0) && page_offset.is_none() {
+        errors.insert("page".to_string(), "is too large".to_string());
+    }
+    if offset.is_some_and(|offset| i64::try_from(offset).is_err()) {
+        errors.insert("offset".to_string(), "is too large".to_string());
+    }
+
+    if !errors.is_empty() {
+        return Err(ApiError::ValidationError(errors));
+    }
+
+    let style = match (offset, limit) {
+        (Some(offset), _) => PageStyle::Offset { limit: size, offset },
+        (None, Some(_)) => PageStyle::Cursor { limit: size },
+        (None, None) if cursor.is_some() => PageStyle::Cursor { limit: size },
+        (None, None) => PageStyle::Page {
+            page: page.unwrap_or(1),
+            per_page: size,
+            offset: page_offset.unwrap_or_default(),
+        },
+    };
+
+    Ok(CountryListParams {
+        filter: CountryFilter {
+            regions,
+            currency,
+            has_currency,
+            min_population,
+            max_population,
+            min_gdp,
+            max_gdp,
+            capital_missing,
+            refreshed_since,
+            expr,
+            include_stale,
+        },
+        sort,
+        style,
+        cursor,
+    })
+}
+
+fn non_empty_param(
+    field: &str,
+    value: Option<String>,
+    errors: &mut HashMap<String, String>,
+) -> Option<String> {
+    let value = value?.trim().to_string();
+
+    if value.is_empty() {
+        errors.insert(field.to_string(), "must not be empty".to_string());
+        return None;
+    }
+
+    Some(value)
+}
+
+fn number_param(
+    field: &str,
+    value: Option<String>,
+    errors: &mut HashMap<String, String>,
+) -> Option<u64> {
+    parsed_param(field, value, errors, "must be a non-negative integer")
+}
+
+fn parsed_param<T: FromStr>(
+    field: &str,
+    value: Option<String>,
+    errors: &mut HashMap<String, String>,
+    message: &str,
+) -> Option<T> {
+    let value = non_empty_param(field, value, errors)?;
+
+    match value.parse() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            errors.insert(field.to_string(), message.to_string());
+            None
+        }
+    }
+}
+
+fn float_param(
+    field: &str,
+    value: Option<String>,
+    errors: &mut HashMap<String, String>,
+) -> Option<f64> {
+    let value = parsed_param::<f64>(field, value, errors, "must be a number")?;
+
+    if !value.is_finite() {
+        errors.insert(field.to_string(), "must be a number".to_string());
+        return None;
+    }
+
+    Some(value)
+}
+
+fn bool_param(
+    field: &str,
+    value: Option<String>,
+    errors: &mut HashMap<String, String>,
+) -> Option<bool> {
+    parsed_param(field, value, errors, "must be true or false")
+}
+
+// Rebuilds the request URL with the pagination parameters swapped out,
+// keeping every other parameter exactly as the client sent it.
+fn page_link(req: &HttpRequest, params: &[(&str, String)], rel: &str) -> String {
+    let mut pairs: Vec<String> = req
+        .query_string()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .filter(|pair| {
+            let key = pair.split('=').next().unwrap_or_default();
+            !PAGINATION_PARAMS.contains(&key)
+        })
+        .map(str::to_string)
+        .collect();
+
+    pairs.extend(params.iter().map(|(key, value)| format!("{}={}", key, value)));
+
+    format!("<{}?{}>; rel=\"{}\"", req.path(), pairs.join("&"), rel)
+}
+
+#[post("/countries")]
+async fn create_country(
+    pool: web::Data<DbPool>,
+    service: web::Data<CountryService>,
+    body: web::Json<CountryPayload>,
+) -> Result<impl Responder, ApiError> {
+    let payload = body.into_inner();
+
+    let errors = validate_country_payload(&payload, true);
+    if !errors.is_empty() {
+        return Err(ApiError::ValidationError(errors));
+    }
+
+    let name = payload.name.clone().unwrap_or_default().trim().to_string();
+    if repository::find_by_name(&pool, &name).await?.is_some() {
+        return Err(ApiError::CountryExists);
+    }
+
+    let mut country = CountryInsert {
+        name,
+        capital: None,
+        region: None,
+        population: 0,
+        currency_code: None,
+        exchange_rate: None,
+        estimated_gdp: None,
+        gdp_model: None,
+        flag_url: None,
+        currencies: Vec::new(),
+    };
+    let lookup_rate = payload.currency_code.is_some() && payload.exchange_rate.is_none();
+    let estimate = payload.estimated_gdp.is_none();
+    apply_country_payload(&mut country, payload);
+    derive_country_fields(&pool, &service, &mut country, lookup_rate, estimate).await?;
+
+    repository::create_manual(&pool, &country).await?;
+
+    let country = repository::find_by_name(&pool, &country.name)
+        .await?
+        .ok_or(ApiError::InternalError)?;
+
+    Ok(HttpResponse::Created()
+        .insert_header((header::LOCATION, format!("/countries/{}", country.name)))
+        .json(country))
+}
+
+#[patch("/countries/{name}")]
+async fn update_country(
+    pool: web::Data<DbPool>,
+    service: web::Data<CountryService>,
+    name: web::Path<String>,
+    body: web::Json<CountryPayload>,
+) -> Result<impl Responder, ApiError> {
+    let payload = body.into_inner();
+
+    let errors = validate_country_payload(&payload, false);
+    if !errors.is_empty() {
+        return Err(ApiError::ValidationError(errors));
+    }
+
+    let existing = repository::find_by_name(&pool, &name)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    // The next refresh would silently overwrite any edit to an upstream
+    // country; corrections to those go through overrides.
+    if existing.source != MANUAL_SOURCE {
+        return Err(ApiError::UpstreamCountry(existing.name));
+    }
+
+    let mut country = CountryInsert {
+        name: existing.name,
+        capital: existing.capital,
+        region: existing.region,
+        population: existing.population,
+        currency_code: existing.currency_code,
+        exchange_rate: existing.exchange_rate,
+        estimated_gdp: existing.estimated_gdp,
+        gdp_model: existing.gdp_model,
+        flag_url: existing.flag_url,
+        currencies: existing.currencies,
+    };
+
+    let currency_changed = payload
+        .currency_code
+        .as_ref()
+        .is_some_and(|code| Some(code.to_uppercase()) != country.currency_code);
+    let population_changed = payload.population.is_some_and(|population| population != country.population);
+    let lookup_rate = currency_changed && payload.exchange_rate.is_none();
+    let estimate = payload.estimated_gdp.is_none()
+        && country.gdp_model.as_deref() != Some(MANUAL_GDP_MODEL)
+        && (currency_changed || population_changed || payload.exchange_rate.is_some());
 
-    Ok(HttpResponse::Ok().json(countries))
+    apply_country_payload(&mut country, payload);
+    derive_country_fields(&pool, &service, &mut country, lookup_rate, estimate).await?;
+
+    repository::update_manual(&pool, existing.id, &country).await?;
+
+    let country = repository::find_by_name(&pool, &country.name)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(country))
+}
+
+// Fills in what a refresh would derive for an edited country: the latest
+// stored exchange rate for its currency, and a GDP estimate from the
+// configured model. Callers skip the estimate for explicitly entered GDP.
+async fn derive_country_fields(
+    pool: &DbPool,
+    service: &CountryService,
+    country: &mut CountryInsert,
+    lookup_rate: bool,
+    estimate: bool,
+) -> Result<(), ApiError> {
+    if lookup_rate {
+        country.exchange_rate = match &country.currency_code {
+            Some(code) => repository::find_latest_rate(pool, code).await?,
+            None => None,
+        };
+    }
+
+    if estimate {
+        let gdp = service.estimate_gdp(
+            &country.name,
+            country.population,
+            country.currency_code.is_some(),
+            country.exchange_rate,
+        );
+        country.estimated_gdp = gdp.estimated_gdp;
+        country.gdp_model = gdp.gdp_model;
+    }
+
+    Ok(())
+}
+
+fn validate_country_payload(payload: &CountryPayload, creating: bool) -> HashMap<String, String> {
+    let mut errors = HashMap::new();
+
+    match (&payload.name, creating) {
+        (Some(name), true) if name.trim().is_empty() => {
+            errors.insert("name".to_string(), "must not be empty".to_string());
+        }
+        (None, true) => {
+            errors.insert("name".to_string(), "is required".to_string());
+        }
+        (Some(_), false) => {
+            errors.insert("name".to_string(), "cannot be changed".to_string());
+        }
+        _ => {}
+    }
+
+    match payload.population {
+        Some(population) if population < 0 => {
+            errors.insert("population".to_string(), "must not be negative".to_string());
+        }
+        None if creating => {
+            errors.insert("population".to_string(), "is required".to_string());
+        }
+        _ => {}
+    }
+
+    if let Some(code) = &payload.currency_code {
+        if code.len() != 3 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+            errors.insert("currency_code".to_string(), "must be a 3-letter currency code".to_string());
+        }
+    }
+
+    if payload.exchange_rate.is_some_and(|rate| !rate.is_finite() || rate <= 0.0) {
+        errors.insert("exchange_rate".to_string(), "must be a positive number".to_string());
+    }
+
+    if payload.estimated_gdp.is_some_and(|gdp| !gdp.is_finite() || gdp < 0.0) {
+        errors.insert("estimated_gdp".to_string(), "must not be negative".to_string());
+    }
+
+    for (field, value) in [
+        ("capital", &payload.capital),
+        ("region", &payload.region),
+        ("flag_url", &payload.flag_url),
+    ] {
+        if value.as_deref().is_some_and(|value| value.trim().is_empty()) {
+            errors.insert(field.to_string(), "must not be empty".to_string());
+        }
+    }
+
+    errors
+}
+
+fn apply_country_payload(country: &mut CountryInsert, payload: CountryPayload) {
+    if let Some(capital) = payload.capital {
+        country.capital = Some(capital);
+    }
+    if let Some(region) = payload.region {
+        country.region = Some(region);
+    }
+    if let Some(population) = payload.population {
+        country.population = population;
+    }
+    if let Some(code) = payload.currency_code {
+        let code = code.to_uppercase();
+        set_primary_currency(&mut country.currencies, &code);
+        country.currency_code = Some(code);
+    }
+    if let Some(exchange_rate) = payload.exchange_rate {
+        country.exchange_rate = Some(exchange_rate);
+    }
+    if let Some(estimated_gdp) = payload.estimated_gdp {
+        country.estimated_gdp = Some(estimated_gdp);
+        country.gdp_model = Some(MANUAL_GDP_MODEL.to_string());
+    }
+    if let Some(flag_url) = payload.flag_url {
+        country.flag_url = Some(flag_url);
+    }
+}
+
+// Makes `code` the primary currency. Secondary currencies are kept, and one
+// that is promoted keeps its stored name and symbol; only the old primary
+// entry is dropped.
+fn set_primary_currency(currencies: &mut Vec<CountryCurrency>, code: &str) {
+    if currencies.iter().any(|currency| currency.is_primary && currency.code == code) {
+        return;
+    }
+
+    currencies.retain(|currency| !currency.is_primary);
+
+    let primary = match currencies.iter().position(|currency| currency.code == code) {
+        Some(position) => CountryCurrency {
+            is_primary: true,
+            ..currencies.remove(position)
+        },
+        None => CountryCurrency {
+            code: code.to_string(),
+            name: None,
+            symbol: None,
+            is_primary: true,
+        },
+    };
+
+    currencies.insert(0, primary);
 }
 
 #[get("/countries/image")]
@@ -91,6 +822,105 @@ async fn get_country_by_name(
     Ok(HttpResponse::Ok().json(country))
 }
 
+#[get("/countries/{name}/history")]
+async fn get_country_history(
+    pool: web::Data<DbPool>,
+    name: web::Path<String>,
+    query: web::Query<HistoryQuery>,
+) -> Result<impl Responder, ApiError> {
+    let mut errors = HashMap::new();
+
+    let from = query.from.as_deref().and_then(|value| {
+        let parsed = parse_datetime_param(value, false);
+        if parsed.is_none() {
+            errors.insert("from".to_string(), "must be a date (YYYY-MM-DD) or RFC 3339 timestamp".to_string());
+        }
+        parsed
+    });
+    let to = query.to.as_deref().and_then(|value| {
+        let parsed = parse_datetime_param(value, true);
+        if parsed.is_none() {
+            errors.insert("to".to_string(), "must be a date (YYYY-MM-DD) or RFC 3339 timestamp".to_string());
+        }
+        parsed
+    });
+
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            errors.insert("to".to_string(), "must not be earlier than from".to_string());
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(ApiError::ValidationError(errors));
+    }
+
+    let country = repository::find_by_name(&pool, &name)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let history = repository::find_history(&pool, &country.name, from, to).await?;
+
+    Ok(HttpResponse::Ok().json(HistoryResponse {
+        name: country.name,
+        history,
+    }))
+}
+
+#[put("/countries/{name}/overrides")]
+async fn put_country_overrides(
+    pool: web::Data<DbPool>,
+    service: web::Data<CountryService>,
+    name: web::Path<String>,
+    body: web::Json<CountryOverrides>,
+) -> Result<impl Responder, ApiError> {
+    let overrides = body.into_inner();
+    let mut errors = HashMap::new();
+
+    for (field, value) in [
+        ("capital", &overrides.capital),
+        ("region", &overrides.region),
+        ("flag_url", &overrides.flag_url),
+    ] {
+        if value.as_deref().is_some_and(|value| value.trim().is_empty()) {
+            errors.insert(field.to_string(), "must not be empty".to_string());
+        }
+    }
+    if overrides.population.is_some_and(|population| population < 0) {
+        errors.insert("population".to_string(), "must not be negative".to_string());
+    }
+
+    if !errors.is_empty() {
+        return Err(ApiError::ValidationError(errors));
+    }
+
+    let country = repository::find_by_name(&pool, &name)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    // GDP follows an overridden population now rather than at the next
+    // refresh. Explicitly entered GDP figures are left alone.
+    let gdp = overrides
+        .population
+        .filter(|_| country.gdp_model.as_deref() != Some(MANUAL_GDP_MODEL))
+        .map(|population| {
+            service.estimate_gdp(
+                &country.name,
+                population,
+                country.currency_code.is_some(),
+                country.exchange_rate,
+            )
+        });
+
+    repository::replace_overrides(&pool, &country.name, &overrides, gdp.as_ref()).await?;
+
+    let country = repository::find_by_name(&pool, &country.name)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(country))
+}
+
 #[delete("/countries/{name}")]
 async fn delete_country(
     pool: web::Data<DbPool>,
@@ -107,18 +937,386 @@ async fn delete_country(
     }))
 }
 
+#[get("/convert")]
+async fn convert_currency(
+    pool: web::Data<DbPool>,
+    query: web::Query<ConvertQuery>,
+) -> Result<impl Responder, ApiError> {
+    let mut errors = HashMap::new();
+
+    let mut currency_param = |field: &str, value: Option<&str>| {
+        match value.map(|value| value.trim().to_uppercase()) {
+            Some(code) if code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()) => Some(code),
+            Some(_) => {
+                errors.insert(field.to_string(), "must be a 3-letter currency code".to_string());
+                None
+            }
+            None => {
+                errors.insert(field.to_string(), "is required".to_string());
+                None
+            }
+        }
+    };
+    let from = currency_param("from", query.from.as_deref());
+    let to = currency_param("to", query.to.as_deref());
+
+    let amount = match query.amount.as_deref().map(str::parse::<f64>) {
+        Some(Ok(amount)) if amount.is_finite() => Some(amount),
+        Some(_) => {
+            errors.insert("amount".to_string(), "must be a number".to_string());
+            None
+        }
+        None => {
+            errors.insert("amount".to_string(), "is required".to_string());
+            None
+        }
+    };
+
+    let date = match query.date.as_deref() {
+        Some(value) => match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            Ok(date) => Some(date),
+            Err(_) => {
+                errors.insert("date".to_string(), "must be a date (YYYY-MM-DD)".to_string());
+                None
+            }
+        },
+        None => Some(Utc::now().date_naive()),
+    };
+
+    let (Some(from), Some(to), Some(amount), Some(date)) = (from, to, amount, date) else {
+        return Err(ApiError::ValidationError(errors));
+    };
+
+    let (rate_date, from_rate, to_rate) = repository::find_conversion_rates(&pool, &from, &to, date)
+        .await?
+        .ok_or(ApiError::RateNotFound)?;
+
+    // Both rates are per USD, so the cross rate goes through USD.
+    let rate = to_rate / from_rate;
+
+    Ok(HttpResponse::Ok().json(ConvertResponse {
+        from,
+        to,
+        amount,
+        rate,
+        result: amount * rate,
+        rate_date,
+    }))
+}
+
 #[get("/status")]
-async fn get_status(pool: web::Data<DbPool>) -> Result<impl Responder, ApiError> {
+async fn get_status(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    scheduler: web::Data<SchedulerState>,
+) -> Result<impl Responder, ApiError> {
     let metadata = repository::get_metadata(&pool).await?;
+    let previous_run = repository::find_latest_refresh_job(&pool, RefreshTrigger::Scheduled).await?;
 
-    Ok(HttpResponse::Ok().json(metadata))
+    let rates_age_secs = metadata
+        .rates_as_of
+        .map(|as_of| (Utc::now() - as_of).num_seconds());
+
+    Ok(HttpResponse::Ok().json(StatusResponse {
+        metadata,
+        rates_age_secs,
+        scheduler: SchedulerStatus {
+            enabled: config.refresh_cron.is_some() || config.refresh_interval_secs.is_some_and(|secs| secs > 0),
+            next_run_at: scheduler.next_run_at().map(|dt| dt.to_rfc3339()),
+            previous_run,
+        },
+    }))
 }
 
 pub fn configure_routes(cfg: &mut web::ServiceConfig) {
-    cfg.service(refresh_countries)
+    // Malformed bodies and query strings get the same error shape as failed
+    // field validation.
+    let json_config = web::JsonConfig::default().error_handler(|err, _| {
+        let mut errors = HashMap::new();
+        errors.insert("body".to_string(), err.to_string());
+        ApiError::ValidationError(errors).into()
+    });
+
+    let query_config = web::QueryConfig::default().error_handler(|err, _| {
+        let mut errors = HashMap::new();
+        errors.insert("query".to_string(), err.to_string());
+        ApiError::ValidationError(errors).into()
+    });
+
+    cfg.app_data(json_config)
+        .app_data(query_config)
+        .service(refresh_countries)
+        .service(get_refresh_job)
+        .service(get_refresh_snapshot)
+        .service(get_refresh_changes)
         .service(get_countries)
+        .service(create_country)
         .service(get_summary_image)
         .service(get_country_by_name)
+        .service(update_country)
+        .service(get_country_history)
+        .service(put_country_overrides)
         .service(delete_country)
+        .service(convert_currency)
         .service(get_status);
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn parse(query: &str) -> Result<CountryListParams, HashMap<String, String>> {
+        let query = web::Query::<CountryQuery>::from_query(query).unwrap().into_inner();
+
+        match parse_country_query(query) {
+            Ok(params) => Ok(params),
+            Err(ApiError::ValidationError(errors)) => Err(errors),
+            Err(other) => panic!("unexpected error: {}", other),
+        }
+    }
+
+    fn errors(query: &str) -> Vec<(String, String)> {
+        let mut errors: Vec<_> = parse(query).err().unwrap().into_iter().collect();
+        errors.sort();
+        errors
+    }
+
+    fn error(field: &str, message: &str) -> (String, String) {
+        (field.to_string(), message.to_string())
+    }
+
+    #[test]
+    fn no_paging_parameters_return_the_first_full_page() {
+        assert!(matches!(
+            parse("").unwrap().style,
+            PageStyle::Page { page: 1, per_page: MAX_PAGE_SIZE, offset: 0 }
+        ));
+        assert!(matches!(
+            parse("region=Africa").unwrap().style,
+            PageStyle::Page { page: 1, per_page: MAX_PAGE_SIZE, offset: 0 }
+        ));
+    }
+
+    #[test]
+    fn page_parameters_compute_the_offset() {
+        assert!(matches!(
+            parse("page=3&per_page=20").unwrap().style,
+            PageStyle::Page { page: 3, per_page: 20, offset: 40 }
+        ));
+        assert!(matches!(
+            parse("page=2").unwrap().style,
+            PageStyle::Page { page: 2, per_page: DEFAULT_PAGE_SIZE, offset: DEFAULT_PAGE_SIZE }
+        ));
+        assert!(matches!(
+            parse("per_page=10").unwrap().style,
+            PageStyle::Page { page: 1, per_page: 10, offset: 0 }
+        ));
+    }
+
+    #[test]
+    fn limit_selects_offset_or_cursor_paging() {
+        assert!(matches!(
+            parse("limit=10&offset=30").unwrap().style,
+            PageStyle::Offset { limit: 10, offset: 30 }
+        ));
+        assert!(matches!(parse("limit=10").unwrap().style, PageStyle::Cursor { limit: 10 }));
+
+        let params = parse("cursor=abcd").unwrap();
+        assert!(matches!(params.style, PageStyle::Cursor { limit: DEFAULT_PAGE_SIZE }));
+        assert_eq!(params.cursor.as_deref(), Some("abcd"));
+    }
+
+    #[test]
+    fn overflowing_pages_and_offsets_are_rejected() {
+        assert_eq!(
+            errors(&format!("page={}&per_page=100", u64::MAX)),
+            vec![error("page", "is too large")]
+        );
+        assert_eq!(
+            errors(&format!("page={}&per_page=2", i64::MAX as u64)),
+            vec![error("page", "is too large")]
+        );
+        assert_eq!(
+            errors(&format!("limit=10&offset={}", u64::MAX)),
+            vec![error("offset", "is too large")]
+        );
+        assert!(parse(&format!("limit=10&offset={}", i64::MAX)).is_ok());
+    }
+
+    #[test]
+    fn paging_parameters_are_validated_together() {
+        assert_eq!(
+            errors("page=0&per_page=0"),
+            vec![
+                error("page", "must be at least 1"),
+                error("per_page", &format!("must be between 1 and {}", MAX_PAGE_SIZE)),
+            ]
+        );
+        assert_eq!(
+            errors("page=1&limit=10"),
+            vec![error("page", "cannot be combined with limit, offset or cursor")]
+        );
+        assert_eq!(
+            errors("cursor=ab&offset=1"),
+            vec![error("cursor", "cannot be combined with offset")]
+        );
+        assert_eq!(
+            errors("limit=-1&offset=x"),
+            vec![
+                error("limit", "must be a non-negative integer"),
+                error("offset", "must be a non-negative integer"),
+            ]
+        );
+    }
+
+    #[test]
+    fn nulls_applies_to_the_requested_sort() {
+        let sort = parse("sort=-capital&nulls=first").unwrap().sort.unwrap();
+        assert_eq!(sort.keys, vec![(crate::models::CountryField::Capital, true)]);
+        assert_eq!(sort.nulls, Some(NullsOrder::First));
+
+        assert_eq!(errors("nulls=last"), vec![error("nulls", "requires sort")]);
+        assert_eq!(errors("sort=name&nulls=middle"), vec![error("nulls", "must be first or last")]);
+        // A bad sort is reported on its own rather than also blaming nulls.
+        assert_eq!(
+            errors("sort=name,name&nulls=last"),
+            vec![error("sort", "field 'name' is listed more than once")]
+        );
+    }
+
+    #[test]
+    fn every_invalid_parameter_gets_its_own_message() {
+        assert_eq!(
+            errors("colour=red&currency=US1&region=&sort=flag&has_currency=yes"),
+            vec![
+                error("colour", "is not a recognised query parameter"),
+                error("currency", "must be a 3-letter currency code"),
+                error("has_currency", "must be true or false"),
+                error("region", "must not be empty"),
+                error(
+                    "sort",
+                    "unknown field 'flag', expected one of name, capital, region, population, currency_code, exchange_rate, estimated_gdp, gdp_per_capita, last_refreshed_at",
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn whitespace_only_parameters_count_as_empty() {
+        assert_eq!(
+            errors("currency=%20%20&filter=%20"),
+            vec![error("currency", "must not be empty"), error("filter", "must not be empty")]
+        );
+    }
+
+    #[test]
+    fn currency_codes_are_normalised_to_upper_case() {
+        assert_eq!(parse("currency=ngn").unwrap().filter.currency.as_deref(), Some("NGN"));
+        assert_eq!(errors("currency=NGNX"), vec![error("currency", "must be a 3-letter currency code")]);
+    }
+
+    #[test]
+    fn range_list_and_presence_filters_are_parsed() {
+        let filter = parse(
+            "region=Africa,%20Europe&min_population=10&max_population=20&min_gdp=1.5&max_gdp=2e3\
+             &has_currency=false&capital_missing=true&refreshed_since=2024-03-01&include_stale=true",
+        )
+        .unwrap()
+        .filter;
+
+        assert_eq!(filter.regions, vec!["Africa", "Europe"]);
+        assert_eq!((filter.min_population, filter.max_population), (Some(10), Some(20)));
+        assert_eq!((filter.min_gdp, filter.max_gdp), (Some(1.5), Some(2000.0)));
+        assert_eq!(filter.has_currency, Some(false));
+        assert_eq!(filter.capital_missing, Some(true));
+        assert_eq!(
+            filter.refreshed_since,
+            Some(Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap())
+        );
+        assert!(filter.include_stale);
+    }
+
+    #[test]
+    fn stale_countries_are_excluded_by_default() {
+        assert!(!parse("").unwrap().filter.include_stale);
+    }
+
+    #[test]
+    fn inverted_and_malformed_ranges_are_rejected() {
+        assert_eq!(
+            errors("min_population=20&max_population=10&min_gdp=5&max_gdp=1"),
+            vec![
+                error("max_gdp", "must not be less than min_gdp"),
+                error("max_population", "must not be less than min_population"),
+            ]
+        );
+        assert_eq!(
+            errors("min_population=1.5&max_gdp=NaN&min_gdp=inf"),
+            vec![
+                error("max_gdp", "must be a number"),
+                error("min_gdp", "must be a number"),
+                error("min_population", "must be an integer"),
+            ]
+        );
+    }
+
+    #[test]
+    fn region_lists_and_dates_are_validated() {
+        assert_eq!(
+            errors("region=Africa,,Asia&refreshed_since=yesterday"),
+            vec![
+                error("refreshed_since", "must be a date (YYYY-MM-DD) or RFC 3339 timestamp"),
+                error("region", "must be a comma-separated list of regions"),
+            ]
+        );
+    }
+
+    fn currency(code: &str, is_primary: bool) -> CountryCurrency {
+        CountryCurrency {
+            code: code.to_string(),
+            name: Some(format!("{} name", code)),
+            symbol: Some(format!("{} symbol", code)),
+            is_primary,
+        }
+    }
+
+    #[test]
+    fn setting_a_new_primary_currency_keeps_the_secondary_ones() {
+        let mut currencies = vec![currency("ZWL", true), currency("USD", false), currency("ZAR", false)];
+
+        set_primary_currency(&mut currencies, "ZWG");
+
+        assert_eq!(
+            currencies,
+            vec![
+                CountryCurrency {
+                    code: "ZWG".to_string(),
+                    name: None,
+                    symbol: None,
+                    is_primary: true,
+                },
+                currency("USD", false),
+                currency("ZAR", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn promoting_a_secondary_currency_keeps_its_name_and_symbol() {
+        let mut currencies = vec![currency("ZWL", true), currency("USD", false), currency("ZAR", false)];
+
+        set_primary_currency(&mut currencies, "ZAR");
+
+        assert_eq!(currencies, vec![currency("ZAR", true), currency("USD", false)]);
+    }
+
+    #[test]
+    fn setting_the_current_primary_currency_changes_nothing() {
+        let mut currencies = vec![currency("EUR", true), currency("USD", false)];
+
+        set_primary_currency(&mut currencies, "EUR");
+
+        assert_eq!(currencies, vec![currency("EUR", true), currency("USD", false)]);
+    }
+}
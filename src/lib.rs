@@ -0,0 +1,8 @@
+pub mod config;
+pub mod db;
+pub mod error;
+pub mod filter_expr;
+pub mod handlers;
+pub mod models;
+pub mod services;
+pub mod utils;
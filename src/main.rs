@@ -1,12 +1,6 @@
-mod config;
-mod db;
-mod error;
-mod handlers;
-mod models;
-mod services;
-mod utils;
-
 use actix_web::{middleware::Logger, web, App, HttpServer};
+use hng_backend_2::services::{CountryService, RefreshSchedule, RefreshScheduler, SchedulerState};
+use hng_backend_2::{config, db, handlers};
 use std::fs;
 
 #[actix_web::main]
@@ -15,6 +9,8 @@ async fn main() -> std::io::Result<()> {
     env_logger::init();
 
     let config = config::Config::from_env();
+    let schedule = RefreshSchedule::from_config(&config)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
     fs::create_dir_all("cache").expect("Failed to create cache directory");
 
     let pool = db::create_pool(&config.database_url)
@@ -28,6 +24,33 @@ async fn main() -> std::io::Result<()> {
         .expect("Failed to run migrations");
     println!("Migrations completed");
 
+    // Only clean up when no replica holds the refresh lock, otherwise the
+    // active jobs belong to a refresh that is still running elsewhere.
+    if let Ok(Some(lock)) = db::refresh_lock::RefreshLock::try_acquire(&pool).await {
+        match db::repository::fail_interrupted_refresh_jobs(&pool).await {
+            Ok(0) => {}
+            Ok(count) => log::warn!("Marked {} interrupted refresh jobs as failed", count),
+            Err(e) => log::error!("Failed to clean up interrupted refresh jobs: {:?}", e),
+        }
+
+        if let Err(e) = lock.release().await {
+            log::error!("Failed to release refresh lock: {:?}", e);
+        }
+    }
+
+    let country_service = web::Data::new(CountryService::new(&config, &pool));
+    let scheduler_state = web::Data::new(SchedulerState::default());
+
+    if let Some(schedule) = schedule {
+        let scheduler = RefreshScheduler::new(schedule, &config, country_service.clone().into_inner());
+        let pool = pool.clone();
+        let state = scheduler_state.clone();
+        tokio::spawn(async move {
+            scheduler.run(pool, &state).await;
+        });
+        log::info!("Refresh scheduler started");
+    }
+
     let server_host = config.server_host.clone();
     let server_port = config.server_port;
 
@@ -42,6 +65,8 @@ async fn main() -> std::io::Result<()> {
             .wrap(Logger::default())
             .app_data(web::Data::new(pool.clone()))
             .app_data(web::Data::new(config.clone()))
+            .app_data(country_service.clone())
+            .app_data(scheduler_state.clone())
             .configure(handlers::configure_routes)
     })
     .bind((server_host.as_str(), server_port))?
@@ -1,8 +1,12 @@
+use crate::filter_expr::FilterExpr;
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::types::chrono::NaiveDateTime;
 use sqlx::FromRow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::marker::PhantomData;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Country {
@@ -14,8 +18,13 @@ pub struct Country {
     pub currency_code: Option<String>,
     pub exchange_rate: Option<f64>,
     pub estimated_gdp: Option<f64>,
+    pub gdp_model: Option<String>,
     pub flag_url: Option<String>,
     pub last_refreshed_at: DateTime<Utc>,
+    pub stale_since: Option<DateTime<Utc>>,
+    pub source: String,
+    pub currencies: Vec<CountryCurrency>,
+    pub overridden_fields: Vec<String>,
 }
 
 impl FromRow<'_, sqlx::mysql::MySqlRow> for Country {
@@ -24,6 +33,7 @@ impl FromRow<'_, sqlx::mysql::MySqlRow> for Country {
         
         let naive_dt: NaiveDateTime = row.try_get("last_refreshed_at")?;
         let dt = DateTime::<Utc>::from_naive_utc_and_offset(naive_dt, Utc);
+        let stale_since: Option<NaiveDateTime> = row.try_get("stale_since")?;
         
         Ok(Country {
             id: row.try_get("id")?,
@@ -34,12 +44,301 @@ impl FromRow<'_, sqlx::mysql::MySqlRow> for Country {
             currency_code: row.try_get("currency_code")?,
             exchange_rate: row.try_get("exchange_rate")?,
             estimated_gdp: row.try_get("estimated_gdp")?,
+            gdp_model: row.try_get("gdp_model")?,
             flag_url: row.try_get("flag_url")?,
             last_refreshed_at: dt,
+            stale_since: stale_since.map(|dt| DateTime::<Utc>::from_naive_utc_and_offset(dt, Utc)),
+            source: row.try_get("source")?,
+            currencies: Vec::new(),
+            overridden_fields: Vec::new(),
         })
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CountryCurrency {
+    pub code: String,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub is_primary: bool,
+}
+
+impl FromRow<'_, sqlx::mysql::MySqlRow> for CountryCurrency {
+    fn from_row(row: &sqlx::mysql::MySqlRow) -> Result<Self, sqlx::Error> {
+        use sqlx::Row;
+
+        Ok(CountryCurrency {
+            code: row.try_get("code")?,
+            name: row.try_get("name")?,
+            symbol: row.try_get("symbol")?,
+            is_primary: row.try_get("is_primary")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CountryOverrides {
+    pub capital: Option<String>,
+    pub region: Option<String>,
+    pub population: Option<i64>,
+    pub flag_url: Option<String>,
+}
+
+impl CountryOverrides {
+    pub fn fields(&self) -> Vec<String> {
+        let mut fields = Vec::new();
+        if self.capital.is_some() {
+            fields.push("capital".to_string());
+        }
+        if self.region.is_some() {
+            fields.push("region".to_string());
+        }
+        if self.population.is_some() {
+            fields.push("population".to_string());
+        }
+        if self.flag_url.is_some() {
+            fields.push("flag_url".to_string());
+        }
+        fields
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields().is_empty()
+    }
+
+    pub fn apply(&self, country: &mut UpstreamCountry) {
+        if let Some(capital) = &self.capital {
+            country.capital = Some(capital.clone());
+        }
+        if let Some(region) = &self.region {
+            country.region = Some(region.clone());
+        }
+        if let Some(population) = self.population {
+            country.population = population;
+        }
+        if let Some(flag_url) = &self.flag_url {
+            country.flag_url = Some(flag_url.clone());
+        }
+    }
+}
+
+impl FromRow<'_, sqlx::mysql::MySqlRow> for CountryOverrides {
+    fn from_row(row: &sqlx::mysql::MySqlRow) -> Result<Self, sqlx::Error> {
+        use sqlx::Row;
+
+        Ok(CountryOverrides {
+            capital: row.try_get("capital")?,
+            region: row.try_get("region")?,
+            population: row.try_get("population")?,
+            flag_url: row.try_get("flag_url")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CountryFilter {
+    pub regions: Vec<String>,
+    pub currency: Option<String>,
+    pub has_currency: Option<bool>,
+    pub min_population: Option<i64>,
+    pub max_population: Option<i64>,
+    pub min_gdp: Option<f64>,
+    pub max_gdp: Option<f64>,
+    pub capital_missing: Option<bool>,
+    pub refreshed_since: Option<DateTime<Utc>>,
+    pub expr: Option<FilterExpr>,
+    pub include_stale: bool,
+}
+
+// The country fields that `sort=` and `filter=` may name. Each maps to a
+// fixed SQL expression over the countries table, so only these ever reach the
+// generated queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountryField {
+    Name,
+    Capital,
+    Region,
+    Population,
+    CurrencyCode,
+    ExchangeRate,
+    EstimatedGdp,
+    GdpPerCapita,
+    GdpModel,
+    Source,
+    LastRefreshedAt,
+}
+
+impl CountryField {
+    pub const SORTABLE: [CountryField; 9] = [
+        CountryField::Name,
+        CountryField::Capital,
+        CountryField::Region,
+        CountryField::Population,
+        CountryField::CurrencyCode,
+        CountryField::ExchangeRate,
+        CountryField::EstimatedGdp,
+        CountryField::GdpPerCapita,
+        CountryField::LastRefreshedAt,
+    ];
+
+    pub const FILTERABLE: [CountryField; 10] = [
+        CountryField::Name,
+        CountryField::Capital,
+        CountryField::Region,
+        CountryField::Population,
+        CountryField::CurrencyCode,
+        CountryField::ExchangeRate,
+        CountryField::EstimatedGdp,
+        CountryField::GdpModel,
+        CountryField::Source,
+        CountryField::LastRefreshedAt,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CountryField::Name => "name",
+            CountryField::Capital => "capital",
+            CountryField::Region => "region",
+            CountryField::Population => "population",
+            CountryField::CurrencyCode => "currency_code",
+            CountryField::ExchangeRate => "exchange_rate",
+            CountryField::EstimatedGdp => "estimated_gdp",
+            CountryField::GdpPerCapita => "gdp_per_capita",
+            CountryField::GdpModel => "gdp_model",
+            CountryField::Source => "source",
+            CountryField::LastRefreshedAt => "last_refreshed_at",
+        }
+    }
+
+    pub fn column(&self) -> &'static str {
+        match self {
+            CountryField::GdpPerCapita => "(estimated_gdp / NULLIF(population, 0))",
+            other => other.as_str(),
+        }
+    }
+
+    pub fn parse_among(value: &str, allowed: &[CountryField]) -> Option<Self> {
+        allowed.iter().copied().find(|field| field.as_str() == value)
+    }
+
+    // For error messages listing the fields that would have been accepted.
+    pub fn list(fields: &[CountryField]) -> String {
+        fields.iter().map(|field| field.as_str()).collect::<Vec<_>>().join(", ")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NullsOrder {
+    First,
+    Last,
+}
+
+impl NullsOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NullsOrder::First => "first",
+            NullsOrder::Last => "last",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "first" => Some(NullsOrder::First),
+            "last" => Some(NullsOrder::Last),
+            _ => None,
+        }
+    }
+}
+
+// A `sort=region,-population,name` ordering. Without an explicit `nulls`,
+// NULLs sort as MySQL does: first when ascending, last when descending.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortSpec {
+    pub keys: Vec<(CountryField, bool)>,
+    pub nulls: Option<NullsOrder>,
+}
+
+impl SortSpec {
+    pub fn parse(sort: &str) -> Result<Self, String> {
+        // The original fixed sort values keep working.
+        let sort = match sort {
+            "gdp_desc" => "-estimated_gdp",
+            "gdp_asc" => "estimated_gdp",
+            "population_desc" => "-population",
+            "population_asc" => "population",
+            other => other,
+        };
+
+        let mut keys: Vec<(CountryField, bool)> = Vec::new();
+
+        for part in sort.split(',') {
+            let part = part.trim();
+            let (name, descending) = match part.strip_prefix('-') {
+                Some(name) => (name, true),
+                None => (part.strip_prefix('+').unwrap_or(part), false),
+            };
+
+            if name.is_empty() {
+                return Err("must be a comma-separated list of fields, each optionally prefixed with -".to_string());
+            }
+
+            let field = CountryField::parse_among(name, &CountryField::SORTABLE).ok_or_else(|| {
+                format!("unknown field '{}', expected one of {}", name, CountryField::list(&CountryField::SORTABLE))
+            })?;
+
+            if keys.iter().any(|(existing, _)| *existing == field) {
+                return Err(format!("field '{}' is listed more than once", name));
+            }
+
+            keys.push((field, descending));
+        }
+
+        Ok(SortSpec { keys, nulls: None })
+    }
+
+    // Stable text form, used to tie keyset cursors to the ordering they came from.
+    pub fn canonical(&self) -> String {
+        let keys: Vec<String> = self
+            .keys
+            .iter()
+            .map(|(field, descending)| format!("{}{}", if *descending { "-" } else { "" }, field.as_str()))
+            .collect();
+
+        match self.nulls {
+            Some(nulls) => format!("{};nulls={}", keys.join(","), nulls.as_str()),
+            None => keys.join(","),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PageRequest {
+    Offset { limit: u64, offset: u64 },
+    Cursor { limit: u64, cursor: Option<String> },
+}
+
+#[derive(Debug, Clone)]
+pub struct CountryPage {
+    pub countries: Vec<Country>,
+    pub total: i64,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+}
+
+// Body of POST /countries and PATCH /countries/{name}. Every field is optional
+// here so both endpoints can report all missing or invalid fields at once.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CountryPayload {
+    pub name: Option<String>,
+    pub capital: Option<String>,
+    pub region: Option<String>,
+    pub population: Option<i64>,
+    pub currency_code: Option<String>,
+    pub exchange_rate: Option<f64>,
+    pub estimated_gdp: Option<f64>,
+    pub flag_url: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct CountryInsert {
     pub name: String,
@@ -49,13 +348,68 @@ pub struct CountryInsert {
     pub currency_code: Option<String>,
     pub exchange_rate: Option<f64>,
     pub estimated_gdp: Option<f64>,
+    pub gdp_model: Option<String>,
     pub flag_url: Option<String>,
+    pub currencies: Vec<CountryCurrency>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GdpEstimate {
+    pub estimated_gdp: Option<f64>,
+    pub gdp_model: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CountryHistoryEntry {
+    pub refresh_job_id: u64,
+    pub recorded_at: DateTime<Utc>,
+    pub population: i64,
+    pub exchange_rate: Option<f64>,
+    pub estimated_gdp: Option<f64>,
+}
+
+impl FromRow<'_, sqlx::mysql::MySqlRow> for CountryHistoryEntry {
+    fn from_row(row: &sqlx::mysql::MySqlRow) -> Result<Self, sqlx::Error> {
+        use sqlx::Row;
+
+        let recorded_at: NaiveDateTime = row.try_get("recorded_at")?;
+
+        Ok(CountryHistoryEntry {
+            refresh_job_id: row.try_get("refresh_job_id")?,
+            recorded_at: DateTime::<Utc>::from_naive_utc_and_offset(recorded_at, Utc),
+            population: row.try_get("population")?,
+            exchange_rate: row.try_get("exchange_rate")?,
+            estimated_gdp: row.try_get("estimated_gdp")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub old: serde_json::Value,
+    pub new: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CountryDiff {
+    pub name: String,
+    pub changes: BTreeMap<String, FieldChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshDiff {
+    pub reconcile_policy: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<CountryDiff>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RefreshMetadata {
     pub total_countries: i32,
     pub last_refreshed_at: DateTime<Utc>,
+    pub rate_provider: Option<String>,
+    pub rates_as_of: Option<DateTime<Utc>>,
 }
 
 impl FromRow<'_, sqlx::mysql::MySqlRow> for RefreshMetadata {
@@ -64,15 +418,18 @@ impl FromRow<'_, sqlx::mysql::MySqlRow> for RefreshMetadata {
         
         let naive_dt: NaiveDateTime = row.try_get("last_refreshed_at")?;
         let dt = DateTime::<Utc>::from_naive_utc_and_offset(naive_dt, Utc);
+        let rates_as_of: Option<NaiveDateTime> = row.try_get("rates_as_of")?;
         
         Ok(RefreshMetadata {
             total_countries: row.try_get("total_countries")?,
             last_refreshed_at: dt,
+            rate_provider: row.try_get("rate_provider")?,
+            rates_as_of: rates_as_of.map(|dt| DateTime::<Utc>::from_naive_utc_and_offset(dt, Utc)),
         })
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CountryApiResponse {
     pub name: String,
     pub capital: Option<String>,
@@ -82,12 +439,427 @@ pub struct CountryApiResponse {
     pub currencies: Option<Vec<Currency>>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Currency {
     pub code: Option<String>,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CountryV3ApiResponse {
+    pub name: CountryV3Name,
+    pub capital: Option<Vec<String>>,
+    pub region: Option<String>,
+    pub population: i64,
+    pub flags: Option<CountryV3Flags>,
+    pub currencies: Option<OrderedMap<CurrencyV3>>,
+}
+
+// A JSON object kept in document order. restcountries v3 keys currencies by
+// code and lists the primary one first, which a sorted map would lose.
+#[derive(Debug, Clone)]
+pub struct OrderedMap<V>(pub Vec<(String, V)>);
+
+impl<V> Default for OrderedMap<V> {
+    fn default() -> Self {
+        OrderedMap(Vec::new())
+    }
+}
+
+impl<'de, V: Deserialize<'de>> Deserialize<'de> for OrderedMap<V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct OrderedMapVisitor<V>(PhantomData<V>);
+
+        impl<'de, V: Deserialize<'de>> Visitor<'de> for OrderedMapVisitor<V> {
+            type Value = OrderedMap<V>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(OrderedMap(entries))
+            }
+        }
+
+        deserializer.deserialize_map(OrderedMapVisitor(PhantomData))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CountryV3Name {
+    pub common: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CountryV3Flags {
+    pub png: Option<String>,
+    pub svg: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CurrencyV3 {
+    pub name: Option<String>,
+    pub symbol: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ExchangeRateApiResponse {
+    pub base_code: Option<String>,
+    pub time_last_update_unix: Option<i64>,
     pub rates: HashMap<String, f64>,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone)]
+pub struct UpstreamCountry {
+    pub name: String,
+    pub capital: Option<String>,
+    pub region: Option<String>,
+    pub population: i64,
+    pub flag_url: Option<String>,
+    pub currencies: Vec<Currency>,
+}
+
+impl From<CountryApiResponse> for UpstreamCountry {
+    fn from(country: CountryApiResponse) -> Self {
+        UpstreamCountry {
+            name: country.name,
+            capital: country.capital,
+            region: country.region,
+            population: country.population,
+            flag_url: country.flag,
+            currencies: country.currencies.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<CountryV3ApiResponse> for UpstreamCountry {
+    fn from(country: CountryV3ApiResponse) -> Self {
+        UpstreamCountry {
+            name: country.name.common,
+            capital: country.capital.and_then(|capitals| capitals.into_iter().next()),
+            region: country.region,
+            population: country.population,
+            flag_url: country.flags.and_then(|flags| flags.svg.or(flags.png)),
+            currencies: country
+                .currencies
+                .unwrap_or_default()
+                .0
+                .into_iter()
+                .map(|(code, currency)| Currency {
+                    code: Some(code),
+                    name: currency.name,
+                    symbol: currency.symbol,
+                })
+                .collect(),
+        }
+    }
+}
+
+// Combined country data is archived in the v2 shape so snapshots replay it.
+impl From<&UpstreamCountry> for CountryApiResponse {
+    fn from(country: &UpstreamCountry) -> Self {
+        CountryApiResponse {
+            name: country.name.clone(),
+            capital: country.capital.clone(),
+            region: country.region.clone(),
+            population: country.population,
+            flag: country.flag_url.clone(),
+            currencies: Some(country.currencies.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExchangeRates {
+    pub base_code: String,
+    pub rates: HashMap<String, f64>,
+    pub provider: String,
+    pub as_of: DateTime<Utc>,
+}
+
+impl ExchangeRateApiResponse {
+    pub fn into_rates(self, provider: &str) -> ExchangeRates {
+        ExchangeRates {
+            base_code: self.base_code.unwrap_or_else(|| "USD".to_string()),
+            rates: self.rates,
+            provider: provider.to_string(),
+            as_of: self
+                .time_last_update_unix
+                .and_then(|secs| DateTime::from_timestamp(secs, 0))
+                .unwrap_or_else(Utc::now),
+        }
+    }
+}
+
+impl ExchangeRates {
+    // Rebases the table onto USD so tables from different providers can be
+    // stored side by side and crossed through USD.
+    pub fn per_usd(&self) -> Option<HashMap<String, f64>> {
+        let usd = if self.base_code == "USD" {
+            1.0
+        } else {
+            *self.rates.get("USD")?
+        };
+
+        let mut rates: HashMap<String, f64> = self
+            .rates
+            .iter()
+            .map(|(code, rate)| (code.clone(), rate / usd))
+            .collect();
+        rates.insert("USD".to_string(), 1.0);
+
+        Some(rates)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RefreshJobStatus {
+    Queued,
+    Fetching,
+    Writing,
+    Rendering,
+    Done,
+    Failed,
+    Skipped,
+}
+
+impl RefreshJobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RefreshJobStatus::Queued => "queued",
+            RefreshJobStatus::Fetching => "fetching",
+            RefreshJobStatus::Writing => "writing",
+            RefreshJobStatus::Rendering => "rendering",
+            RefreshJobStatus::Done => "done",
+            RefreshJobStatus::Failed => "failed",
+            RefreshJobStatus::Skipped => "skipped",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "queued" => Some(RefreshJobStatus::Queued),
+            "fetching" => Some(RefreshJobStatus::Fetching),
+            "writing" => Some(RefreshJobStatus::Writing),
+            "rendering" => Some(RefreshJobStatus::Rendering),
+            "done" => Some(RefreshJobStatus::Done),
+            "failed" => Some(RefreshJobStatus::Failed),
+            "skipped" => Some(RefreshJobStatus::Skipped),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RefreshTrigger {
+    Manual,
+    Scheduled,
+}
+
+impl RefreshTrigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RefreshTrigger::Manual => "manual",
+            RefreshTrigger::Scheduled => "scheduled",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "manual" => Some(RefreshTrigger::Manual),
+            "scheduled" => Some(RefreshTrigger::Scheduled),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamFetchStats {
+    pub source: String,
+    pub latency_ms: u64,
+    pub payload_bytes: u64,
+    #[serde(default)]
+    pub not_modified: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshJob {
+    pub id: u64,
+    pub status: RefreshJobStatus,
+    pub triggered_by: RefreshTrigger,
+    pub replay_of: Option<u64>,
+    pub total_countries: i32,
+    pub countries_processed: i32,
+    pub rate_provider: Option<String>,
+    pub rates_as_of: Option<DateTime<Utc>>,
+    pub upstream_stats: Vec<UpstreamFetchStats>,
+    pub reconcile_policy: Option<String>,
+    pub reconciled_countries: Vec<String>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl FromRow<'_, sqlx::mysql::MySqlRow> for RefreshJob {
+    fn from_row(row: &sqlx::mysql::MySqlRow) -> Result<Self, sqlx::Error> {
+        use sqlx::Row;
+
+        let status: String = row.try_get("status")?;
+        let status = RefreshJobStatus::parse(&status).ok_or_else(|| sqlx::Error::ColumnDecode {
+            index: "status".to_string(),
+            source: format!("unknown refresh job status: {}", status).into(),
+        })?;
+
+        let triggered_by: String = row.try_get("triggered_by")?;
+        let triggered_by = RefreshTrigger::parse(&triggered_by).ok_or_else(|| sqlx::Error::ColumnDecode {
+            index: "triggered_by".to_string(),
+            source: format!("unknown refresh trigger: {}", triggered_by).into(),
+        })?;
+
+        let rates_as_of: Option<NaiveDateTime> = row.try_get("rates_as_of")?;
+        let upstream_stats: Option<String> = row.try_get("upstream_stats")?;
+        let upstream_stats = match upstream_stats {
+            Some(json) => serde_json::from_str(&json).map_err(|e| sqlx::Error::ColumnDecode {
+                index: "upstream_stats".to_string(),
+                source: Box::new(e),
+            })?,
+            None => Vec::new(),
+        };
+        let reconciled_countries: Option<String> = row.try_get("reconciled_countries")?;
+        let reconciled_countries = match reconciled_countries {
+            Some(json) => serde_json::from_str(&json).map_err(|e| sqlx::Error::ColumnDecode {
+                index: "reconciled_countries".to_string(),
+                source: Box::new(e),
+            })?,
+            None => Vec::new(),
+        };
+        let created_at: NaiveDateTime = row.try_get("created_at")?;
+        let started_at: Option<NaiveDateTime> = row.try_get("started_at")?;
+        let finished_at: Option<NaiveDateTime> = row.try_get("finished_at")?;
+
+        Ok(RefreshJob {
+            id: row.try_get("id")?,
+            status,
+            triggered_by,
+            replay_of: row.try_get("replay_of")?,
+            total_countries: row.try_get("total_countries")?,
+            countries_processed: row.try_get("countries_processed")?,
+            rate_provider: row.try_get("rate_provider")?,
+            rates_as_of: rates_as_of.map(|dt| DateTime::<Utc>::from_naive_utc_and_offset(dt, Utc)),
+            upstream_stats,
+            reconcile_policy: row.try_get("reconcile_policy")?,
+            reconciled_countries,
+            error: row.try_get("error")?,
+            created_at: DateTime::<Utc>::from_naive_utc_and_offset(created_at, Utc),
+            started_at: started_at.map(|dt| DateTime::<Utc>::from_naive_utc_and_offset(dt, Utc)),
+            finished_at: finished_at.map(|dt| DateTime::<Utc>::from_naive_utc_and_offset(dt, Utc)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v3_currencies_keep_upstream_order() {
+        let country: CountryV3ApiResponse = serde_json::from_str(
+            r#"{
+                "name": {"common": "Zimbabwe"},
+                "population": 15000000,
+                "currencies": {
+                    "USD": {"name": "United States dollar", "symbol": "$"},
+                    "BWP": {"name": "Botswana pula", "symbol": "P"},
+                    "EUR": {"name": "Euro", "symbol": "€"}
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let codes: Vec<Option<String>> = UpstreamCountry::from(country)
+            .currencies
+            .into_iter()
+            .map(|currency| currency.code)
+            .collect();
+
+        assert_eq!(
+            codes,
+            vec![Some("USD".to_string()), Some("BWP".to_string()), Some("EUR".to_string())]
+        );
+    }
+
+    #[test]
+    fn v3_currencies_may_be_null() {
+        let country: CountryV3ApiResponse = serde_json::from_str(
+            r#"{"name": {"common": "Antarctica"}, "population": 1000, "currencies": null}"#,
+        )
+        .unwrap();
+
+        assert!(UpstreamCountry::from(country).currencies.is_empty());
+    }
+
+    #[test]
+    fn sort_spec_parses_multiple_keys_and_directions() {
+        let sort = SortSpec::parse("region, -population,+name").unwrap();
+
+        assert_eq!(
+            sort.keys,
+            vec![
+                (CountryField::Region, false),
+                (CountryField::Population, true),
+                (CountryField::Name, false),
+            ]
+        );
+        assert_eq!(sort.nulls, None);
+    }
+
+    #[test]
+    fn sort_spec_keeps_the_legacy_sort_values() {
+        assert_eq!(SortSpec::parse("gdp_desc").unwrap().keys, vec![(CountryField::EstimatedGdp, true)]);
+        assert_eq!(SortSpec::parse("gdp_asc").unwrap().keys, vec![(CountryField::EstimatedGdp, false)]);
+        assert_eq!(SortSpec::parse("population_desc").unwrap().keys, vec![(CountryField::Population, true)]);
+        assert_eq!(SortSpec::parse("population_asc").unwrap().keys, vec![(CountryField::Population, false)]);
+    }
+
+    #[test]
+    fn sort_spec_rejects_unknown_empty_and_repeated_fields() {
+        assert_eq!(
+            SortSpec::parse("name,-flag_url"),
+            Err("unknown field 'flag_url', expected one of name, capital, region, population, currency_code, exchange_rate, estimated_gdp, gdp_per_capita, last_refreshed_at".to_string())
+        );
+        assert_eq!(
+            SortSpec::parse("name,"),
+            Err("must be a comma-separated list of fields, each optionally prefixed with -".to_string())
+        );
+        assert_eq!(
+            SortSpec::parse("-"),
+            Err("must be a comma-separated list of fields, each optionally prefixed with -".to_string())
+        );
+        assert_eq!(
+            SortSpec::parse("name,-name"),
+            Err("field 'name' is listed more than once".to_string())
+        );
+    }
+
+    #[test]
+    fn sort_spec_canonical_form_is_stable() {
+        let mut sort = SortSpec::parse(" +region , -gdp_per_capita").unwrap();
+        assert_eq!(sort.canonical(), "region,-gdp_per_capita");
+
+        sort.nulls = Some(NullsOrder::Last);
+        assert_eq!(sort.canonical(), "region,-gdp_per_capita;nulls=last");
+
+        assert_eq!(SortSpec::parse("gdp_desc").unwrap().canonical(), "-estimated_gdp");
+    }
+}
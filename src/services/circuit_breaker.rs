@@ -0,0 +1,135 @@
+use crate::error::ApiError;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+pub struct CircuitBreakers {
+    threshold: u32,
+    cooldown: Duration,
+    hosts: Mutex<HashMap<String, BreakerState>>,
+}
+
+impl CircuitBreakers {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Once the cool-down has elapsed the next call goes through as a trial;
+    // a failure re-opens the circuit straight away.
+    pub fn check(&self, host: &str) -> Result<(), ApiError> {
+        let hosts = self.hosts.lock().unwrap();
+
+        let Some(opened_at) = hosts.get(host).and_then(|state| state.opened_at) else {
+            return Ok(());
+        };
+
+        let elapsed = opened_at.elapsed();
+        if elapsed >= self.cooldown {
+            return Ok(());
+        }
+
+        Err(ApiError::CircuitOpen {
+            upstream: host.to_string(),
+            retry_after_secs: (self.cooldown - elapsed).as_secs().max(1),
+        })
+    }
+
+    pub fn record_success(&self, host: &str) {
+        self.hosts.lock().unwrap().remove(host);
+    }
+
+    pub fn record_failure(&self, host: &str) {
+        if self.threshold == 0 {
+            return;
+        }
+
+        let mut hosts = self.hosts.lock().unwrap();
+        let state = hosts.entry(host.to_string()).or_default();
+        state.consecutive_failures += 1;
+
+        if state.consecutive_failures >= self.threshold {
+            if state.opened_at.is_none() {
+                log::warn!(
+                    "Opening circuit for {} after {} consecutive failures",
+                    host,
+                    state.consecutive_failures
+                );
+            }
+            state.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_threshold_consecutive_failures() {
+        let breakers = CircuitBreakers::new(3, Duration::from_secs(60));
+
+        breakers.record_failure("restcountries.com");
+        breakers.record_failure("restcountries.com");
+        assert!(breakers.check("restcountries.com").is_ok());
+
+        breakers.record_failure("restcountries.com");
+        match breakers.check("restcountries.com") {
+            Err(ApiError::CircuitOpen { upstream, retry_after_secs }) => {
+                assert_eq!(upstream, "restcountries.com");
+                assert!(retry_after_secs > 0 && retry_after_secs <= 60);
+            }
+            other => panic!("expected an open circuit, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn success_resets_the_failure_count() {
+        let breakers = CircuitBreakers::new(2, Duration::from_secs(60));
+
+        breakers.record_failure("open.er-api.com");
+        breakers.record_success("open.er-api.com");
+        breakers.record_failure("open.er-api.com");
+
+        assert!(breakers.check("open.er-api.com").is_ok());
+    }
+
+    #[test]
+    fn hosts_are_tracked_separately() {
+        let breakers = CircuitBreakers::new(1, Duration::from_secs(60));
+
+        breakers.record_failure("restcountries.com");
+
+        assert!(breakers.check("restcountries.com").is_err());
+        assert!(breakers.check("open.er-api.com").is_ok());
+    }
+
+    #[test]
+    fn allows_a_trial_call_after_the_cooldown() {
+        let breakers = CircuitBreakers::new(1, Duration::ZERO);
+
+        breakers.record_failure("restcountries.com");
+
+        assert!(breakers.check("restcountries.com").is_ok());
+    }
+
+    #[test]
+    fn zero_threshold_never_opens() {
+        let breakers = CircuitBreakers::new(0, Duration::from_secs(60));
+
+        for _ in 0..10 {
+            breakers.record_failure("restcountries.com");
+        }
+
+        assert!(breakers.check("restcountries.com").is_ok());
+    }
+}
@@ -1,48 +1,191 @@
+use crate::config::{Config, ReconcilePolicy};
+use crate::db::refresh_lock::RefreshLock;
 use crate::db::repository;
 use crate::error::ApiError;
-use crate::models::{CountryApiResponse, CountryInsert, ExchangeRateApiResponse};
+use crate::models::{
+    Country, CountryCurrency, CountryDiff, CountryFilter, CountryInsert, CountryOverrides, ExchangeRates, FieldChange, GdpEstimate, RefreshDiff,
+    RefreshJobStatus, UpstreamCountry,
+};
+use crate::services::external_api::{UpstreamData, UpstreamFailure};
+use crate::services::gdp_estimator::{self, GdpEstimator};
+use crate::services::providers::stored::STORED_PROVIDER_NAME;
+use crate::services::snapshot_archive::SnapshotArchive;
 use crate::services::{ExternalApiService, ImageGenerator};
-use rand::Rng;
-use sqlx::{MySql, Pool};
+use sqlx::{MySql, Pool, Transaction};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+
+// `source` of countries created through the API rather than by a refresh.
+pub const MANUAL_SOURCE: &str = "manual";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshSource {
+    Upstream,
+    Snapshot(u64),
+}
 
 pub struct CountryService {
     external_api: ExternalApiService,
+    snapshots: SnapshotArchive,
+    gdp_estimator: Box<dyn GdpEstimator>,
+    reconcile_policy: ReconcilePolicy,
 }
 
 impl CountryService {
-    pub fn new(timeout_secs: u64) -> Self {
+    pub fn new(config: &Config, pool: &Pool<MySql>) -> Self {
         Self {
-            external_api: ExternalApiService::new(timeout_secs),
+            external_api: ExternalApiService::new(config, pool),
+            snapshots: SnapshotArchive::new(&config.snapshot_dir, config.snapshot_retention),
+            gdp_estimator: gdp_estimator::from_config(config),
+            reconcile_policy: config.reconcile_policy,
+        }
+    }
+
+    pub fn snapshots(&self) -> &SnapshotArchive {
+        &self.snapshots
+    }
+
+    pub async fn acquire_refresh_lock(pool: &Pool<MySql>) -> Result<RefreshLock, ApiError> {
+        if let Some(lock) = RefreshLock::try_acquire(pool).await? {
+            return Ok(lock);
+        }
+
+        let running = repository::find_active_refresh_job(pool).await?;
+
+        Err(ApiError::RefreshInProgress {
+            job_id: running.as_ref().map(|job| job.id),
+            started_at: running.map(|job| job.started_at.unwrap_or(job.created_at)),
+        })
+    }
+
+    pub async fn run_refresh_job(
+        &self,
+        pool: &Pool<MySql>,
+        job_id: u64,
+        lock: RefreshLock,
+        source: RefreshSource,
+    ) {
+        let (status, error) = match self.refresh_countries(pool, job_id, source).await {
+            Ok((total_countries, _)) => {
+                log::info!("Refresh job {} finished with {} countries", job_id, total_countries);
+                (RefreshJobStatus::Done, None)
+            }
+            Err(e) => {
+                log::error!("Refresh job {} failed: {:?}", job_id, e);
+                (RefreshJobStatus::Failed, Some(e.detail()))
+            }
+        };
+
+        if let Err(e) = repository::finish_refresh_job(pool, job_id, status, error.as_deref()).await {
+            log::error!("Failed to record outcome of refresh job {}: {:?}", job_id, e);
+        }
+
+        if let Err(e) = lock.release().await {
+            log::error!("Failed to release refresh lock for job {}: {:?}", job_id, e);
         }
     }
 
     pub async fn refresh_countries(
         &self,
         pool: &Pool<MySql>,
+        job_id: u64,
+        source: RefreshSource,
     ) -> Result<(i32, chrono::DateTime<chrono::Utc>), ApiError> {
-        let (countries_data, rates_data) = self.external_api.fetch_all_data().await?;
+        repository::update_refresh_job_status(pool, job_id, RefreshJobStatus::Fetching).await?;
+        let data = match self.load_data(source).await {
+            Ok(data) => data,
+            Err(failure) => {
+                repository::update_refresh_job_upstream_stats(pool, job_id, &failure.stats).await?;
+                return Err(failure.error);
+            }
+        };
+        repository::update_refresh_job_upstream_stats(pool, job_id, &data.stats).await?;
 
+        if let Err(e) = self.snapshots.store(job_id, &data).await {
+            log::error!("Failed to archive upstream snapshot for job {}: {:?}", job_id, e);
+        }
+
+        let UpstreamData {
+            countries: mut countries_data,
+            rates: rates_data,
+            ..
+        } = data;
+
+        let total_countries = countries_data.len() as i32;
+        repository::update_refresh_job_rates(pool, job_id, &rates_data.provider, rates_data.as_of).await?;
+        repository::update_refresh_job_status(pool, job_id, RefreshJobStatus::Writing).await?;
+        repository::update_refresh_job_progress(pool, job_id, total_countries, 0).await?;
+
+        let recorded_at = chrono::Utc::now();
         let mut tx = pool.begin().await?;
 
-        for country_api in countries_data.iter() {
-            let country_insert = self.process_country(country_api, &rates_data);
+        // Country and override writes from the API don't take the refresh
+        // lock; locking the rows this diff is based on makes them wait until
+        // the refresh commits.
+        let overrides = repository::find_all_overrides_for_update(&mut tx).await?;
+        apply_overrides(&mut countries_data, &overrides);
+
+        let country_inserts: Vec<CountryInsert> = countries_data
+            .iter()
+            .map(|country_api| self.process_country(country_api, &rates_data, job_id))
+            .collect();
 
-            let existing = repository::find_by_name_case_insensitive(&mut tx, &country_insert.name).await?;
+        let existing = repository::find_all_for_update(&mut tx).await?;
+        let (changes, missing) = diff_countries(self.reconcile_policy, existing, &country_inserts);
 
-            if existing.is_some() {
-                repository::update(&mut tx, &country_insert).await?;
-            } else {
-                repository::insert(&mut tx, &country_insert).await?;
+        // Rates replayed from a snapshot or read back from exchange_rates are
+        // already on record; storing them again would relabel the original
+        // provider's row for that date.
+        let recorded_rates = source != RefreshSource::Upstream || rates_data.provider == STORED_PROVIDER_NAME;
+
+        match rates_data.per_usd() {
+            Some(_) if recorded_rates => log::info!(
+                "Not storing {} rate table from {}: already recorded",
+                rates_data.provider,
+                rates_data.as_of.date_naive()
+            ),
+            Some(rates) => {
+                repository::store_exchange_rates(
+                    &mut tx,
+                    rates_data.as_of.date_naive(),
+                    &rates,
+                    &rates_data.provider,
+                    recorded_at,
+                )
+                .await?
             }
+            None => log::warn!(
+                "Not storing {} rate table: no USD rate to rebase from {}",
+                rates_data.provider,
+                rates_data.base_code
+            ),
         }
 
-        let total_countries = countries_data.len() as i32;
-        repository::update_metadata(&mut tx, total_countries).await?;
+        let mut processed = 0;
+        for chunk in country_inserts.chunks(repository::UPSERT_CHUNK_SIZE) {
+            let ids = repository::upsert_countries(&mut tx, chunk).await?;
+
+            let written: Vec<(u64, &CountryInsert)> = ids.into_iter().zip(chunk).collect();
+
+            repository::replace_currencies_batch(&mut tx, &written).await?;
+            repository::insert_history_batch(&mut tx, job_id, &written, recorded_at).await?;
+
+            processed += chunk.len();
+            repository::update_refresh_job_progress(pool, job_id, total_countries, processed as i32).await?;
+        }
+
+        self.reconcile_missing(&mut tx, &missing, recorded_at).await?;
+        repository::insert_refresh_changes(&mut tx, job_id, &changes).await?;
+
+        repository::update_metadata(&mut tx, total_countries, &rates_data.provider, rates_data.as_of).await?;
 
         tx.commit().await?;
+        repository::update_refresh_job_progress(pool, job_id, total_countries, total_countries).await?;
+        repository::update_refresh_job_reconciliation(pool, job_id, self.reconcile_policy.as_str(), &missing).await?;
 
         let metadata = repository::get_metadata(pool).await?;
 
+        repository::update_refresh_job_status(pool, job_id, RefreshJobStatus::Rendering).await?;
         match self.generate_summary_image(pool).await {
             Ok(_) => log::info!("Summary image generated successfully"),
             Err(e) => log::error!("Failed to generate summary image: {:?}", e),
@@ -51,35 +194,104 @@ impl CountryService {
         Ok((metadata.total_countries, metadata.last_refreshed_at))
     }
 
+    // Runs the fetch and per-country processing of a refresh without writing
+    // anything, and reports how the stored countries would change.
+    pub async fn preview_refresh(
+        &self,
+        pool: &Pool<MySql>,
+        source: RefreshSource,
+    ) -> Result<RefreshDiff, ApiError> {
+        let mut data = self.load_data(source).await.map_err(|failure| failure.error)?;
+
+        let overrides = repository::find_all_overrides(pool).await?;
+        apply_overrides(&mut data.countries, &overrides);
+
+        // There is no job ID yet. Estimates only depend on it when the model
+        // varies per refresh, and then the real values can't be known ahead.
+        let country_inserts: Vec<CountryInsert> = data
+            .countries
+            .iter()
+            .map(|country_api| self.process_country(country_api, &data.rates, 0))
+            .collect();
+
+        let existing = repository::find_all(pool, &all_countries(), None).await?;
+        let (mut diff, _) = diff_countries(self.reconcile_policy, existing, &country_inserts);
+
+        if self.gdp_estimator.varies_per_refresh() {
+            for country in &mut diff.changed {
+                country.changes.remove("estimated_gdp");
+            }
+            diff.changed.retain(|country| !country.changes.is_empty());
+        }
+
+        Ok(diff)
+    }
+
+    async fn load_data(&self, source: RefreshSource) -> Result<UpstreamData, UpstreamFailure> {
+        match source {
+            RefreshSource::Upstream => self.external_api.fetch_all_data().await,
+            RefreshSource::Snapshot(snapshot_job_id) => Ok(self.snapshots.load(snapshot_job_id).await?),
+        }
+    }
+
+    // Applies the reconcile policy to stored countries the upstream no longer
+    // returns.
+    async fn reconcile_missing(
+        &self,
+        tx: &mut Transaction<'_, MySql>,
+        missing: &[String],
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), ApiError> {
+        for name in missing {
+            match self.reconcile_policy {
+                ReconcilePolicy::Keep => {}
+                ReconcilePolicy::Stale => repository::mark_stale(tx, name, now).await?,
+                ReconcilePolicy::Delete => repository::delete_in_tx(tx, name).await?,
+            }
+        }
+
+        if !missing.is_empty() {
+            log::info!(
+                "{} countries missing upstream, policy {}: {}",
+                missing.len(),
+                self.reconcile_policy.as_str(),
+                missing.join(", ")
+            );
+        }
+
+        Ok(())
+    }
+
     fn process_country(
         &self,
-        country_api: &CountryApiResponse,
-        rates: &ExchangeRateApiResponse,
+        country_api: &UpstreamCountry,
+        rates: &ExchangeRates,
+        refresh_id: u64,
     ) -> CountryInsert {
-        let mut rng = rand::thread_rng();
-
         let currency_code = country_api
             .currencies
-            .as_ref()
-            .and_then(|currencies| {
-                if currencies.is_empty() {
-                    None
-                } else {
-                    currencies[0].code.clone()
-                }
-            });
+            .first()
+            .and_then(|currency| currency.code.clone());
 
-        let (exchange_rate, estimated_gdp) = if let Some(ref code) = currency_code {
-            if let Some(&rate) = rates.rates.get(code) {
-                let random_multiplier = rng.gen_range(1000.0..=2000.0);
-                let gdp = (country_api.population as f64 * random_multiplier) / rate;
-                (Some(rate), Some(gdp))
-            } else {
-                (None, None)
+        let mut currencies: Vec<CountryCurrency> = Vec::new();
+        for currency in &country_api.currencies {
+            let Some(code) = &currency.code else { continue };
+            if currencies.iter().any(|existing| &existing.code == code) {
+                continue;
             }
-        } else {
-            (None, Some(0.0))
-        };
+
+            currencies.push(CountryCurrency {
+                code: code.clone(),
+                name: currency.name.clone(),
+                symbol: currency.symbol.clone(),
+                is_primary: currency_code.as_ref() == Some(code),
+            });
+        }
+
+        let exchange_rate = currency_code
+            .as_ref()
+            .and_then(|code| rates.rates.get(code).copied());
+        let gdp = self.estimate(country_api, currency_code.is_some(), exchange_rate, refresh_id);
 
         CountryInsert {
             name: country_api.name.clone(),
@@ -88,8 +300,54 @@ impl CountryService {
             population: country_api.population,
             currency_code,
             exchange_rate,
+            estimated_gdp: gdp.estimated_gdp,
+            gdp_model: gdp.gdp_model,
+            flag_url: country_api.flag_url.clone(),
+            currencies,
+        }
+    }
+
+    // Re-estimates GDP for a country edited through the API, the way the next
+    // refresh would. There is no refresh ID here, so a per-refresh model
+    // draws for refresh 0 until the next refresh replaces the value.
+    pub fn estimate_gdp(
+        &self,
+        name: &str,
+        population: i64,
+        has_currency: bool,
+        exchange_rate: Option<f64>,
+    ) -> GdpEstimate {
+        let country = UpstreamCountry {
+            name: name.to_string(),
+            capital: None,
+            region: None,
+            population,
+            flag_url: None,
+            currencies: Vec::new(),
+        };
+
+        self.estimate(&country, has_currency, exchange_rate, 0)
+    }
+
+    // Countries without a currency get a GDP of 0 and no model.
+    fn estimate(
+        &self,
+        country: &UpstreamCountry,
+        has_currency: bool,
+        exchange_rate: Option<f64>,
+        refresh_id: u64,
+    ) -> GdpEstimate {
+        if !has_currency {
+            return GdpEstimate {
+                estimated_gdp: Some(0.0),
+                gdp_model: None,
+            };
+        }
+
+        let estimated_gdp = self.gdp_estimator.estimate(country, exchange_rate, refresh_id);
+        GdpEstimate {
             estimated_gdp,
-            flag_url: country_api.flag.clone(),
+            gdp_model: estimated_gdp.map(|_| self.gdp_estimator.name().to_string()),
         }
     }
 
@@ -109,4 +367,236 @@ impl CountryService {
         log::info!("Image saved to cache/summary.png");
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+fn all_countries() -> CountryFilter {
+    CountryFilter {
+        include_stale: true,
+        ..Default::default()
+    }
+}
+
+fn apply_overrides(countries: &mut [UpstreamCountry], overrides: &HashMap<String, CountryOverrides>) {
+    for country in countries {
+        if let Some(overrides) = overrides.get(&country.name.to_lowercase()) {
+            overrides.apply(country);
+        }
+    }
+}
+
+// Compares the stored countries with the processed upstream ones. Also
+// returns the stored upstream countries missing from the fetch, whatever
+// the policy; the diff only lists them as removed when the policy acts on
+// them. Countries created through the API are never reconciled, and under
+// the stale policy countries already marked stale are left alone.
+fn diff_countries(
+    policy: ReconcilePolicy,
+    existing: Vec<Country>,
+    country_inserts: &[CountryInsert],
+) -> (RefreshDiff, Vec<String>) {
+    let mut existing_by_name: HashMap<String, Country> = existing
+        .into_iter()
+        .map(|country| (country.name.to_lowercase(), country))
+        .collect();
+
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+
+    for country_insert in country_inserts {
+        match existing_by_name.remove(&country_insert.name.to_lowercase()) {
+            Some(existing) => {
+                let changes = diff_country(&existing, country_insert);
+                if !changes.is_empty() {
+                    changed.push(CountryDiff {
+                        name: existing.name,
+                        changes,
+                    });
+                }
+            }
+            None => added.push(country_insert.name.clone()),
+        }
+    }
+
+    // An empty upstream response is far more likely an outage than every
+    // country disappearing at once.
+    let mut missing: Vec<String> = if country_inserts.is_empty() {
+        log::warn!("Upstream returned no countries, skipping reconciliation");
+        Vec::new()
+    } else {
+        existing_by_name
+            .into_values()
+            .filter(|country| country.source != MANUAL_SOURCE)
+            .filter(|country| {
+                policy != ReconcilePolicy::Stale || country.stale_since.is_none()
+            })
+            .map(|country| country.name)
+            .collect()
+    };
+    missing.sort();
+
+    let removed = match policy {
+        ReconcilePolicy::Keep => Vec::new(),
+        ReconcilePolicy::Stale | ReconcilePolicy::Delete => missing.clone(),
+    };
+
+    let diff = RefreshDiff {
+        reconcile_policy: policy.as_str().to_string(),
+        added,
+        removed,
+        changed,
+    };
+
+    (diff, missing)
+}
+
+fn diff_country(existing: &Country, new: &CountryInsert) -> BTreeMap<String, FieldChange> {
+    let mut changes = BTreeMap::new();
+
+    let mut compare = |field: &str, old: serde_json::Value, new: serde_json::Value| {
+        if old != new {
+            changes.insert(field.to_string(), FieldChange { old, new });
+        }
+    };
+
+    compare("capital", json!(existing.capital), json!(new.capital));
+    compare("region", json!(existing.region), json!(new.region));
+    compare("population", json!(existing.population), json!(new.population));
+    compare("currency_code", json!(existing.currency_code), json!(new.currency_code));
+    compare("exchange_rate", json!(existing.exchange_rate), json!(new.exchange_rate));
+    compare("estimated_gdp", json!(existing.estimated_gdp), json!(new.estimated_gdp));
+    compare("gdp_model", json!(existing.gdp_model), json!(new.gdp_model));
+    compare("flag_url", json!(existing.flag_url), json!(new.flag_url));
+    // Upserting clears stale_since, so a stale country that is back upstream
+    // is reactivated even when none of its data changed.
+    compare("stale_since", json!(existing.stale_since), serde_json::Value::Null);
+    compare(
+        "currencies",
+        json!(existing.currencies.iter().map(|c| &c.code).collect::<Vec<_>>()),
+        json!(new.currencies.iter().map(|c| &c.code).collect::<Vec<_>>()),
+    );
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn stored(name: &str, population: i64) -> Country {
+        Country {
+            id: 1,
+            name: name.to_string(),
+            capital: None,
+            region: Some("Africa".to_string()),
+            population,
+            currency_code: None,
+            exchange_rate: None,
+            estimated_gdp: None,
+            gdp_model: None,
+            flag_url: None,
+            last_refreshed_at: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+            stale_since: None,
+            source: "upstream".to_string(),
+            currencies: Vec::new(),
+            overridden_fields: Vec::new(),
+        }
+    }
+
+    fn upstream(name: &str, population: i64) -> CountryInsert {
+        CountryInsert {
+            name: name.to_string(),
+            capital: None,
+            region: Some("Africa".to_string()),
+            population,
+            currency_code: None,
+            exchange_rate: None,
+            estimated_gdp: None,
+            gdp_model: None,
+            flag_url: None,
+            currencies: Vec::new(),
+        }
+    }
+
+    fn stale(mut country: Country) -> Country {
+        country.stale_since = Some(Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap());
+        country
+    }
+
+    #[test]
+    fn reports_added_and_changed_countries() {
+        let existing = vec![stored("Ghana", 100), stored("Kenya", 200)];
+        let inserts = vec![upstream("ghana", 150), upstream("Kenya", 200), upstream("Togo", 50)];
+
+        let (diff, missing) = diff_countries(ReconcilePolicy::Keep, existing, &inserts);
+
+        assert_eq!(diff.added, vec!["Togo"]);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].name, "Ghana");
+        assert_eq!(
+            diff.changed[0].changes.keys().collect::<Vec<_>>(),
+            vec!["population"]
+        );
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn missing_countries_are_removed_only_when_the_policy_acts() {
+        let existing = || vec![stored("Ghana", 100), stored("Kenya", 200)];
+        let inserts = vec![upstream("Ghana", 100)];
+
+        let (diff, missing) = diff_countries(ReconcilePolicy::Keep, existing(), &inserts);
+        assert!(diff.removed.is_empty());
+        assert_eq!(missing, vec!["Kenya"]);
+
+        let (diff, missing) = diff_countries(ReconcilePolicy::Stale, existing(), &inserts);
+        assert_eq!(diff.removed, vec!["Kenya"]);
+        assert_eq!(missing, vec!["Kenya"]);
+    }
+
+    #[test]
+    fn already_stale_countries_are_not_removed_again() {
+        let existing = || vec![stored("Ghana", 100), stale(stored("Kenya", 200))];
+        let inserts = vec![upstream("Ghana", 100)];
+
+        let (diff, missing) = diff_countries(ReconcilePolicy::Stale, existing(), &inserts);
+        assert!(diff.removed.is_empty());
+        assert!(missing.is_empty());
+
+        // Switching to delete still removes them.
+        let (diff, missing) = diff_countries(ReconcilePolicy::Delete, existing(), &inserts);
+        assert_eq!(diff.removed, vec!["Kenya"]);
+        assert_eq!(missing, vec!["Kenya"]);
+    }
+
+    #[test]
+    fn reactivated_countries_are_reported_as_changed() {
+        let existing = vec![stale(stored("Kenya", 200))];
+        let inserts = vec![upstream("Kenya", 200)];
+
+        let (diff, _) = diff_countries(ReconcilePolicy::Stale, existing, &inserts);
+
+        assert_eq!(diff.changed.len(), 1);
+        let change = &diff.changed[0].changes["stale_since"];
+        assert_eq!(change.old, json!("2024-02-01T00:00:00Z"));
+        assert_eq!(change.new, serde_json::Value::Null);
+    }
+
+    #[test]
+    fn manual_countries_and_empty_fetches_are_never_reconciled() {
+        let mut manual = stored("Atlantis", 10);
+        manual.source = MANUAL_SOURCE.to_string();
+
+        let (diff, missing) = diff_countries(
+            ReconcilePolicy::Delete,
+            vec![manual, stored("Ghana", 100)],
+            &[upstream("Ghana", 100)],
+        );
+        assert!(diff.removed.is_empty());
+        assert!(missing.is_empty());
+
+        let (diff, missing) = diff_countries(ReconcilePolicy::Delete, vec![stored("Ghana", 100)], &[]);
+        assert!(diff.removed.is_empty());
+        assert!(missing.is_empty());
+    }
+}
@@ -1,65 +1,577 @@
+use crate::config::{Config, CountryProviderKind, RateProviderKind};
 use crate::error::ApiError;
-use crate::models::{CountryApiResponse, ExchangeRateApiResponse};
-use reqwest::Client;
-use std::time::Duration;
+use crate::models::{CountryApiResponse, ExchangeRates, UpstreamCountry, UpstreamFetchStats};
+use crate::services::circuit_breaker::CircuitBreakers;
+use crate::services::providers::{
+    CountryProvider, Fetched, OpenErApiProvider, RateProvider, RestCountriesV2Provider,
+    RestCountriesV3Provider, StaticFileProvider, StoredRatesProvider,
+};
+use crate::services::upstream_cache::UpstreamCache;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::header::{
+    HeaderMap, HeaderName, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RETRY_AFTER,
+};
+use reqwest::{Client, StatusCode};
+use serde::de::DeserializeOwned;
+use sqlx::{MySql, Pool};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-pub struct ExternalApiService {
+const COUNTRIES_FIXTURE: &str = "countries.json";
+const EXCHANGE_RATES_FIXTURE: &str = "exchange_rates.json";
+
+enum AttemptError {
+    Retryable { retry_after: Option<Duration> },
+    Fatal,
+}
+
+#[derive(Clone)]
+pub struct UpstreamClient {
     client: Client,
+    timeout: Duration,
+    max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    breakers: Arc<CircuitBreakers>,
+    cache: UpstreamCache,
 }
 
-impl ExternalApiService {
-    pub fn new(timeout_secs: u64) -> Self {
+impl UpstreamClient {
+    pub fn new(config: &Config) -> Self {
+        let timeout = Duration::from_secs(config.external_api_timeout_secs);
         let client = Client::builder()
-            .timeout(Duration::from_secs(timeout_secs))
+            .timeout(timeout)
             .build()
             .expect("Failed to build HTTP client");
 
-        Self { client }
+        Self {
+            client,
+            timeout,
+            max_retries: config.upstream_max_retries,
+            base_delay: Duration::from_millis(config.upstream_retry_base_delay_ms),
+            max_delay: Duration::from_millis(config.upstream_retry_max_delay_ms),
+            breakers: Arc::new(CircuitBreakers::new(
+                config.circuit_breaker_threshold,
+                Duration::from_secs(config.circuit_breaker_cooldown_secs),
+            )),
+            cache: UpstreamCache::new(&config.upstream_cache_dir),
+        }
+    }
+
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        Self {
+            timeout,
+            ..self.clone()
+        }
     }
 
-    pub async fn fetch_countries(&self) -> Result<Vec<CountryApiResponse>, ApiError> {
-        let url = "https://restcountries.com/v2/all?fields=name,capital,region,population,flag,currencies";
-        
-        let response = self.client
-            .get(url)
+    pub async fn get_json<T: DeserializeOwned>(&self, url: &str, source: &str) -> Result<Fetched<T>, ApiError> {
+        self.breakers.check(source)?;
+
+        let mut attempt = 0;
+        loop {
+            let retry_after = match self.try_get_json::<T>(url).await {
+                Ok(body) => {
+                    self.breakers.record_success(source);
+                    return Ok(body);
+                }
+                Err(AttemptError::Retryable { retry_after }) if attempt < self.max_retries => retry_after,
+                Err(_) => {
+                    self.breakers.record_failure(source);
+                    return Err(ApiError::ExternalApi(source.to_string()));
+                }
+            };
+
+            let delay = match retry_after {
+                Some(delay) if delay > self.max_delay => {
+                    log::warn!("{} asked to retry after {:?}, giving up", source, delay);
+                    self.breakers.record_failure(source);
+                    return Err(ApiError::ExternalApi(source.to_string()));
+                }
+                Some(delay) => delay,
+                None => self.backoff(attempt),
+            };
+
+            attempt += 1;
+            log::warn!(
+                "Request to {} failed, retrying in {:?} (attempt {}/{})",
+                source,
+                delay,
+                attempt,
+                self.max_retries
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn try_get_json<T: DeserializeOwned>(&self, url: &str) -> Result<Fetched<T>, AttemptError> {
+        let cached = self.cache.load(url).await;
+
+        let mut request = self.client.get(url).timeout(self.timeout);
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = request
             .send()
             .await
-            .map_err(|_| ApiError::ExternalApiError("restcountries.com".to_string()))?;
+            .map_err(|e| classify_request_error(&e))?;
+
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED {
+            let cached = cached.ok_or(AttemptError::Fatal)?;
+            let data = serde_json::from_slice::<T>(&cached.body).map_err(|_| AttemptError::Fatal)?;
+            log::info!("{} not modified, reusing cached response", url);
+
+            return Ok(Fetched {
+                data,
+                raw: cached.body,
+                payload_bytes: 0,
+                not_modified: true,
+            });
+        }
 
-        if !response.status().is_success() {
-            return Err(ApiError::ExternalApiError("restcountries.com".to_string()));
+        if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+            return Err(AttemptError::Retryable {
+                retry_after: retry_after(response.headers()),
+            });
         }
 
-        response
-            .json::<Vec<CountryApiResponse>>()
+        if !status.is_success() {
+            return Err(AttemptError::Fatal);
+        }
+
+        let etag = header_value(response.headers(), ETAG);
+        let last_modified = header_value(response.headers(), LAST_MODIFIED);
+
+        let bytes = response
+            .bytes()
             .await
-            .map_err(|_| ApiError::ExternalApiError("restcountries.com".to_string()))
+            .map_err(|e| classify_request_error(&e))?;
+
+        let data = serde_json::from_slice::<T>(&bytes).map_err(|_| AttemptError::Fatal)?;
+        self.cache.store(url, etag, last_modified, &bytes).await;
+
+        Ok(Fetched::new(data, bytes.to_vec()))
     }
 
-    pub async fn fetch_exchange_rates(&self) -> Result<ExchangeRateApiResponse, ApiError> {
-        let url = "https://open.er-api.com/v6/latest/USD";
-        
-        let response = self.client
-            .get(url)
-            .send()
-            .await
-            .map_err(|_| ApiError::ExternalApiError("open.er-api.com".to_string()))?;
+    // Exponential backoff with jitter: a random delay between half and all of
+    // base * 2^attempt, capped at the configured maximum.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let millis = backoff_cap(self.base_delay, self.max_delay, attempt).as_millis() as u64;
+
+        Duration::from_millis(rand::thread_rng().gen_range(millis / 2..=millis))
+    }
+}
+
+fn backoff_cap(base_delay: Duration, max_delay: Duration, attempt: u32) -> Duration {
+    base_delay
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(max_delay)
+}
 
-        if !response.status().is_success() {
-            return Err(ApiError::ExternalApiError("open.er-api.com".to_string()));
+fn classify_request_error(e: &reqwest::Error) -> AttemptError {
+    if e.is_timeout() || e.is_connect() {
+        AttemptError::Retryable { retry_after: None }
+    } else {
+        AttemptError::Fatal
+    }
+}
+
+fn header_value(headers: &HeaderMap, name: HeaderName) -> Option<String> {
+    headers.get(name)?.to_str().ok().map(str::to_string)
+}
+
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    (at.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+pub struct UpstreamData {
+    pub countries: Vec<UpstreamCountry>,
+    pub rates: ExchangeRates,
+    pub stats: Vec<UpstreamFetchStats>,
+    pub raw_countries: Vec<u8>,
+    pub raw_rates: Vec<u8>,
+}
+
+// A failed fetch still carries the stats of every upstream call it made, so
+// the job records which source failed or was slow.
+#[derive(Debug)]
+pub struct UpstreamFailure {
+    pub error: ApiError,
+    pub stats: Vec<UpstreamFetchStats>,
+}
+
+impl From<ApiError> for UpstreamFailure {
+    fn from(error: ApiError) -> Self {
+        UpstreamFailure {
+            error,
+            stats: Vec::new(),
+        }
+    }
+}
+
+pub struct ExternalApiService {
+    countries: Vec<Box<dyn CountryProvider>>,
+    rates: Vec<Box<dyn RateProvider>>,
+}
+
+impl ExternalApiService {
+    pub fn new(config: &Config, pool: &Pool<MySql>) -> Self {
+        let client = UpstreamClient::new(config);
+        let countries_client =
+            client.with_timeout(Duration::from_secs(config.countries_api_timeout_secs));
+        let rates_client =
+            client.with_timeout(Duration::from_secs(config.exchange_rates_api_timeout_secs));
+
+        let mut rates: Vec<Box<dyn RateProvider>> = config
+            .rate_providers
+            .iter()
+            .map(|&kind| rate_provider(kind, config, &rates_client))
+            .collect();
+
+        if config.stored_rates_fallback {
+            rates.push(Box::new(StoredRatesProvider::new(pool.clone())));
         }
 
-        response
-            .json::<ExchangeRateApiResponse>()
-            .await
-            .map_err(|_| ApiError::ExternalApiError("open.er-api.com".to_string()))
+        let countries = config
+            .country_providers
+            .iter()
+            .map(|&kind| country_provider(kind, config, &countries_client))
+            .collect();
+
+        Self { countries, rates }
     }
 
-    pub async fn fetch_all_data(
+    // The first country provider is required; any further ones only fill in
+    // what it left out, so their failures are logged and skipped. Every call
+    // adds an entry to `stats`, whether it succeeded or not.
+    pub async fn fetch_countries(
         &self,
-    ) -> Result<(Vec<CountryApiResponse>, ExchangeRateApiResponse), ApiError> {
-        let countries = self.fetch_countries().await?;
-        let rates = self.fetch_exchange_rates().await?;
-        Ok((countries, rates))
+        stats: &mut Vec<UpstreamFetchStats>,
+    ) -> Result<Fetched<Vec<UpstreamCountry>>, ApiError> {
+        let mut combined: Option<Fetched<Vec<UpstreamCountry>>> = None;
+        let mut fetched_from = 0;
+
+        for provider in &self.countries {
+            let started = Instant::now();
+
+            let fetched = match provider.fetch_countries().await {
+                Ok(fetched) => fetched,
+                Err(e) => {
+                    log::warn!(
+                        "Country provider {} failed after {}ms: {:?}",
+                        provider.name(),
+                        started.elapsed().as_millis(),
+                        e
+                    );
+                    stats.push(failure_stats(provider.name(), started, &e));
+                    if combined.is_none() {
+                        return Err(e);
+                    }
+                    continue;
+                }
+            };
+
+            let provider_stats = fetch_stats(provider.name(), started, &fetched);
+            log::info!(
+                "Fetched {} countries from {} in {}ms ({} bytes)",
+                fetched.data.len(),
+                provider_stats.source,
+                provider_stats.latency_ms,
+                provider_stats.payload_bytes
+            );
+            stats.push(provider_stats);
+            fetched_from += 1;
+
+            combined = Some(match combined {
+                None => fetched,
+                Some(mut combined) => {
+                    combine_countries(&mut combined.data, fetched.data);
+                    combined.payload_bytes += fetched.payload_bytes;
+                    combined.not_modified &= fetched.not_modified;
+                    combined
+                }
+            });
+        }
+
+        let mut fetched = combined.ok_or_else(|| ApiError::ExternalApi("countries".to_string()))?;
+
+        // No single upstream body matches combined data, so archive what was built.
+        if fetched_from > 1 {
+            let archived: Vec<CountryApiResponse> = fetched.data.iter().map(CountryApiResponse::from).collect();
+            fetched.raw = serde_json::to_vec(&archived).map_err(|_| ApiError::InternalError)?;
+        }
+
+        Ok(fetched)
+    }
+
+    pub async fn fetch_exchange_rates(
+        &self,
+        stats: &mut Vec<UpstreamFetchStats>,
+    ) -> Result<Fetched<ExchangeRates>, ApiError> {
+        let mut last_error = ApiError::ExternalApi("exchange rates".to_string());
+
+        for provider in &self.rates {
+            let started = Instant::now();
+
+            match provider.fetch_rates().await {
+                Ok(fetched) => {
+                    let provider_stats = fetch_stats(provider.name(), started, &fetched);
+                    let rates = &fetched.data;
+                    log::info!(
+                        "Fetched {} {} exchange rates from {} (as of {}) in {}ms ({} bytes)",
+                        rates.rates.len(),
+                        rates.base_code,
+                        provider_stats.source,
+                        rates.as_of.to_rfc3339(),
+                        provider_stats.latency_ms,
+                        provider_stats.payload_bytes
+                    );
+                    stats.push(provider_stats);
+                    return Ok(fetched);
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Rate provider {} failed after {}ms: {:?}",
+                        provider.name(),
+                        started.elapsed().as_millis(),
+                        e
+                    );
+                    stats.push(failure_stats(provider.name(), started, &e));
+                    last_error = e;
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    pub async fn fetch_all_data(&self) -> Result<UpstreamData, UpstreamFailure> {
+        let mut stats = Vec::new();
+        let mut rates_stats = Vec::new();
+        let (countries, rates) = tokio::join!(
+            self.fetch_countries(&mut stats),
+            self.fetch_exchange_rates(&mut rates_stats)
+        );
+        stats.append(&mut rates_stats);
+
+        let (countries, rates) = match (countries, rates) {
+            (Ok(countries), Ok(rates)) => (countries, rates),
+            (Err(error), _) | (_, Err(error)) => return Err(UpstreamFailure { error, stats }),
+        };
+
+        Ok(UpstreamData {
+            countries: countries.data,
+            rates: rates.data,
+            stats,
+            raw_countries: countries.raw,
+            raw_rates: rates.raw,
+        })
+    }
+}
+
+fn fetch_stats<T>(source: &str, started: Instant, fetched: &Fetched<T>) -> UpstreamFetchStats {
+    UpstreamFetchStats {
+        source: source.to_string(),
+        latency_ms: started.elapsed().as_millis() as u64,
+        payload_bytes: fetched.payload_bytes,
+        not_modified: fetched.not_modified,
+        error: None,
+    }
+}
+
+fn failure_stats(source: &str, started: Instant, error: &ApiError) -> UpstreamFetchStats {
+    UpstreamFetchStats {
+        source: source.to_string(),
+        latency_ms: started.elapsed().as_millis() as u64,
+        payload_bytes: 0,
+        not_modified: false,
+        error: Some(error.detail()),
+    }
+}
+
+// Later providers only fill fields earlier ones left empty, and add
+// countries earlier ones did not return at all.
+fn combine_countries(countries: &mut Vec<UpstreamCountry>, other: Vec<UpstreamCountry>) {
+    let mut by_name: HashMap<String, usize> = countries
+        .iter()
+        .enumerate()
+        .map(|(index, country)| (country.name.to_lowercase(), index))
+        .collect();
+
+    for country in other {
+        let Some(&index) = by_name.get(&country.name.to_lowercase()) else {
+            by_name.insert(country.name.to_lowercase(), countries.len());
+            countries.push(country);
+            continue;
+        };
+
+        let existing = &mut countries[index];
+        existing.capital = existing.capital.take().or(country.capital);
+        existing.region = existing.region.take().or(country.region);
+        existing.flag_url = existing.flag_url.take().or(country.flag_url);
+        if existing.population == 0 {
+            existing.population = country.population;
+        }
+        if existing.currencies.is_empty() {
+            existing.currencies = country.currencies;
+        }
+    }
+}
+
+fn country_provider(
+    kind: CountryProviderKind,
+    config: &Config,
+    client: &UpstreamClient,
+) -> Box<dyn CountryProvider> {
+    let fixture_dir = config.countries_api_url.strip_prefix("file://");
+
+    match (kind, fixture_dir) {
+        (CountryProviderKind::StaticFile, _) => {
+            Box::new(StaticFileProvider::new(&config.countries_file))
+        }
+        (_, Some(dir)) => {
+            Box::new(StaticFileProvider::new(Path::new(dir).join(COUNTRIES_FIXTURE)))
+        }
+        (CountryProviderKind::RestCountriesV2, None) => Box::new(RestCountriesV2Provider::new(
+            client.clone(),
+            &config.countries_api_url,
+        )),
+        (CountryProviderKind::RestCountriesV3, None) => Box::new(RestCountriesV3Provider::new(
+            client.clone(),
+            &config.countries_api_url,
+        )),
+    }
+}
+
+fn rate_provider(
+    kind: RateProviderKind,
+    config: &Config,
+    client: &UpstreamClient,
+) -> Box<dyn RateProvider> {
+    match kind {
+        RateProviderKind::OpenErApi => {
+            if let Some(dir) = config.exchange_rates_api_url.strip_prefix("file://") {
+                return Box::new(StaticFileProvider::new(Path::new(dir).join(EXCHANGE_RATES_FIXTURE)));
+            }
+
+            Box::new(OpenErApiProvider::new(
+                client.clone(),
+                &config.exchange_rates_api_url,
+            ))
+        }
+        RateProviderKind::StaticFile => {
+            Box::new(StaticFileProvider::new(&config.exchange_rates_file))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Currency;
+    use reqwest::header::HeaderValue;
+
+    fn retry_after_header(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let base = Duration::from_millis(500);
+        let max = Duration::from_millis(10_000);
+
+        assert_eq!(backoff_cap(base, max, 0), Duration::from_millis(500));
+        assert_eq!(backoff_cap(base, max, 1), Duration::from_millis(1_000));
+        assert_eq!(backoff_cap(base, max, 4), Duration::from_millis(8_000));
+        assert_eq!(backoff_cap(base, max, 5), max);
+        assert_eq!(backoff_cap(base, max, 64), max);
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds() {
+        assert_eq!(retry_after(&retry_after_header("120")), Some(Duration::from_secs(120)));
+        assert_eq!(retry_after(&retry_after_header(" 0 ")), Some(Duration::ZERO));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn retry_after_accepts_http_dates() {
+        let at = Utc::now() + chrono::Duration::seconds(90);
+        let delay = retry_after(&retry_after_header(&at.to_rfc2822())).unwrap();
+
+        assert!(delay > Duration::from_secs(85) && delay <= Duration::from_secs(90));
+    }
+
+    #[test]
+    fn retry_after_ignores_past_dates_and_garbage() {
+        let past = Utc::now() - chrono::Duration::seconds(90);
+
+        assert_eq!(retry_after(&retry_after_header(&past.to_rfc2822())), None);
+        assert_eq!(retry_after(&retry_after_header("soon")), None);
+        assert_eq!(retry_after(&retry_after_header("-5")), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    fn country(name: &str, capital: Option<&str>, population: i64, currencies: &[&str]) -> UpstreamCountry {
+        UpstreamCountry {
+            name: name.to_string(),
+            capital: capital.map(str::to_string),
+            region: None,
+            population,
+            flag_url: None,
+            currencies: currencies
+                .iter()
+                .map(|code| Currency {
+                    code: Some(code.to_string()),
+                    name: None,
+                    symbol: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn combine_countries_fills_gaps_without_overwriting() {
+        let mut countries = vec![country("Nigeria", Some("Abuja"), 0, &[])];
+        combine_countries(&mut countries, vec![country("NIGERIA", Some("Lagos"), 206_139_589, &["NGN"])]);
+
+        assert_eq!(countries.len(), 1);
+        assert_eq!(countries[0].name, "Nigeria");
+        assert_eq!(countries[0].capital.as_deref(), Some("Abuja"));
+        assert_eq!(countries[0].population, 206_139_589);
+        assert_eq!(countries[0].currencies[0].code.as_deref(), Some("NGN"));
+    }
+
+    #[test]
+    fn combine_countries_appends_countries_missing_from_earlier_providers() {
+        let mut countries = vec![country("Ghana", Some("Accra"), 31_072_940, &["GHS"])];
+        combine_countries(
+            &mut countries,
+            vec![
+                country("Kosovo", Some("Pristina"), 1_775_378, &["EUR"]),
+                country("kosovo", None, 0, &[]),
+            ],
+        );
+
+        let names: Vec<&str> = countries.iter().map(|country| country.name.as_str()).collect();
+        assert_eq!(names, vec!["Ghana", "Kosovo"]);
+    }
+}
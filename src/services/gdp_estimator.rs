@@ -0,0 +1,250 @@
+use crate::config::{Config, GdpModelKind};
+use crate::models::UpstreamCountry;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub trait GdpEstimator: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn estimate(
+        &self,
+        country: &UpstreamCountry,
+        exchange_rate: Option<f64>,
+        refresh_id: u64,
+    ) -> Option<f64>;
+
+    // Whether the same inputs can give a different estimate on another refresh.
+    fn varies_per_refresh(&self) -> bool {
+        false
+    }
+}
+
+pub fn from_config(config: &Config) -> Box<dyn GdpEstimator> {
+    match config.gdp_model {
+        GdpModelKind::SeededRandom => Box::new(SeededRandomEstimator::new(
+            config.gdp_random_seed,
+            config.gdp_random_per_refresh,
+        )),
+        GdpModelKind::FixedMultiplier => {
+            Box::new(FixedMultiplierEstimator::new(config.gdp_fixed_multiplier))
+        }
+        GdpModelKind::PerCapitaCsv => Box::new(
+            PerCapitaCsvEstimator::load(&config.gdp_per_capita_csv)
+                .expect("GDP_PER_CAPITA_CSV must point to a readable CSV file"),
+        ),
+    }
+}
+
+// Draws the 1000-2000 multiplier from an RNG seeded with the configured seed
+// and the country name, so a country keeps its multiplier across refreshes.
+// With `per_refresh` the refresh ID is mixed in as well and every refresh
+// draws a new one.
+pub struct SeededRandomEstimator {
+    seed: u64,
+    per_refresh: bool,
+}
+
+impl SeededRandomEstimator {
+    pub fn new(seed: u64, per_refresh: bool) -> Self {
+        Self { seed, per_refresh }
+    }
+
+    fn country_seed(&self, name: &str, refresh_id: u64) -> u64 {
+        // FNV-1a keeps the seed stable across builds, unlike DefaultHasher.
+        const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        let refresh_id = self.per_refresh.then_some(refresh_id);
+
+        self.seed
+            .to_le_bytes()
+            .into_iter()
+            .chain(refresh_id.into_iter().flat_map(u64::to_le_bytes))
+            .chain(name.to_lowercase().into_bytes())
+            .fold(FNV_OFFSET, |hash, byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+    }
+}
+
+impl GdpEstimator for SeededRandomEstimator {
+    fn name(&self) -> &'static str {
+        "seeded_random"
+    }
+
+    fn estimate(
+        &self,
+        country: &UpstreamCountry,
+        exchange_rate: Option<f64>,
+        refresh_id: u64,
+    ) -> Option<f64> {
+        let rate = exchange_rate?;
+        let mut rng = StdRng::seed_from_u64(self.country_seed(&country.name, refresh_id));
+        let multiplier = rng.gen_range(1000.0..=2000.0);
+
+        Some((country.population as f64 * multiplier) / rate)
+    }
+
+    fn varies_per_refresh(&self) -> bool {
+        self.per_refresh
+    }
+}
+
+pub struct FixedMultiplierEstimator {
+    multiplier: f64,
+}
+
+impl FixedMultiplierEstimator {
+    pub fn new(multiplier: f64) -> Self {
+        Self { multiplier }
+    }
+}
+
+impl GdpEstimator for FixedMultiplierEstimator {
+    fn name(&self) -> &'static str {
+        "fixed_multiplier"
+    }
+
+    fn estimate(
+        &self,
+        country: &UpstreamCountry,
+        exchange_rate: Option<f64>,
+        _refresh_id: u64,
+    ) -> Option<f64> {
+        let rate = exchange_rate?;
+
+        Some((country.population as f64 * self.multiplier) / rate)
+    }
+}
+
+#[derive(Deserialize)]
+struct PerCapitaRecord {
+    name: String,
+    gdp_per_capita_usd: f64,
+}
+
+// Uses real per-capita GDP figures (USD) keyed by country name, so the
+// estimate does not depend on the exchange rate.
+pub struct PerCapitaCsvEstimator {
+    per_capita: HashMap<String, f64>,
+}
+
+impl PerCapitaCsvEstimator {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, csv::Error> {
+        let mut reader = csv::Reader::from_path(path)?;
+        let mut per_capita = HashMap::new();
+
+        for record in reader.deserialize::<PerCapitaRecord>() {
+            let record = record?;
+            per_capita.insert(record.name.to_lowercase(), record.gdp_per_capita_usd);
+        }
+
+        log::info!("Loaded per-capita GDP figures for {} countries", per_capita.len());
+        Ok(Self { per_capita })
+    }
+}
+
+impl GdpEstimator for PerCapitaCsvEstimator {
+    fn name(&self) -> &'static str {
+        "per_capita_csv"
+    }
+
+    fn estimate(
+        &self,
+        country: &UpstreamCountry,
+        _exchange_rate: Option<f64>,
+        _refresh_id: u64,
+    ) -> Option<f64> {
+        let per_capita = self.per_capita.get(&country.name.to_lowercase())?;
+
+        Some(country.population as f64 * per_capita)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn country(name: &str, population: i64) -> UpstreamCountry {
+        UpstreamCountry {
+            name: name.to_string(),
+            capital: None,
+            region: None,
+            population,
+            flag_url: None,
+            currencies: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn seeded_random_is_stable_across_refreshes() {
+        let estimator = SeededRandomEstimator::new(42, false);
+        let nigeria = country("Nigeria", 1_000_000);
+
+        let first = estimator.estimate(&nigeria, Some(2.0), 1);
+        assert!(first.is_some());
+        assert_eq!(estimator.estimate(&nigeria, Some(2.0), 2), first);
+        assert_eq!(estimator.estimate(&country("NIGERIA", 1_000_000), Some(2.0), 3), first);
+        assert!(!estimator.varies_per_refresh());
+    }
+
+    #[test]
+    fn seeded_random_stays_within_the_multiplier_range() {
+        let estimator = SeededRandomEstimator::new(7, false);
+
+        for name in ["Ghana", "Kenya", "Peru", "Chile", "Nepal"] {
+            let gdp = estimator.estimate(&country(name, 1_000), Some(4.0), 1).unwrap();
+            assert!((250_000.0..=500_000.0).contains(&gdp), "{} got {}", name, gdp);
+        }
+    }
+
+    #[test]
+    fn seeded_random_depends_on_seed_and_name() {
+        let ghana = country("Ghana", 1_000_000);
+
+        let a = SeededRandomEstimator::new(1, false).estimate(&ghana, Some(1.0), 1);
+        let b = SeededRandomEstimator::new(2, false).estimate(&ghana, Some(1.0), 1);
+        let c = SeededRandomEstimator::new(1, false).estimate(&country("Kenya", 1_000_000), Some(1.0), 1);
+
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn seeded_random_per_refresh_is_opt_in() {
+        let estimator = SeededRandomEstimator::new(42, true);
+        let ghana = country("Ghana", 1_000_000);
+
+        assert_eq!(estimator.estimate(&ghana, Some(1.0), 1), estimator.estimate(&ghana, Some(1.0), 1));
+        assert_ne!(estimator.estimate(&ghana, Some(1.0), 1), estimator.estimate(&ghana, Some(1.0), 2));
+        assert!(estimator.varies_per_refresh());
+    }
+
+    #[test]
+    fn rate_based_models_need_an_exchange_rate() {
+        let ghana = country("Ghana", 1_000_000);
+
+        assert_eq!(SeededRandomEstimator::new(0, false).estimate(&ghana, None, 1), None);
+        assert_eq!(FixedMultiplierEstimator::new(1500.0).estimate(&ghana, None, 1), None);
+    }
+
+    #[test]
+    fn fixed_multiplier_divides_by_the_rate() {
+        let estimator = FixedMultiplierEstimator::new(1500.0);
+
+        assert_eq!(estimator.estimate(&country("Ghana", 1_000), Some(3.0), 1), Some(500_000.0));
+    }
+
+    #[test]
+    fn per_capita_csv_looks_up_names_case_insensitively() {
+        let path = std::env::temp_dir().join(format!("gdp_per_capita_{}.csv", std::process::id()));
+        std::fs::write(&path, "name,gdp_per_capita_usd\nGhana,2400.5\n").unwrap();
+
+        let estimator = PerCapitaCsvEstimator::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(estimator.estimate(&country("GHANA", 1_000), None, 1), Some(2_400_500.0));
+        assert_eq!(estimator.estimate(&country("Kenya", 1_000), Some(1.0), 1), None);
+    }
+}
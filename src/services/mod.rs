@@ -1,7 +1,14 @@
+pub mod circuit_breaker;
 pub mod external_api;
 pub mod country_service;
+pub mod gdp_estimator;
 pub mod image_generator;
+pub mod providers;
+pub mod scheduler;
+pub mod snapshot_archive;
+pub mod upstream_cache;
 
 pub use external_api::ExternalApiService;
-pub use country_service::CountryService;
-pub use image_generator::ImageGenerator;
\ No newline at end of file
+pub use country_service::{CountryService, RefreshSource};
+pub use image_generator::ImageGenerator;
+pub use scheduler::{RefreshSchedule, RefreshScheduler, SchedulerState};
\ No newline at end of file
@@ -0,0 +1,61 @@
+pub mod open_er_api;
+pub mod restcountries;
+pub mod static_file;
+pub mod stored;
+
+pub use open_er_api::OpenErApiProvider;
+pub use restcountries::{RestCountriesV2Provider, RestCountriesV3Provider};
+pub use static_file::StaticFileProvider;
+pub use stored::StoredRatesProvider;
+
+use crate::error::ApiError;
+use crate::models::{ExchangeRates, UpstreamCountry};
+use async_trait::async_trait;
+
+pub struct Fetched<T> {
+    pub data: T,
+    pub raw: Vec<u8>,
+    pub payload_bytes: u64,
+    pub not_modified: bool,
+}
+
+impl<T> Fetched<T> {
+    pub fn new(data: T, raw: Vec<u8>) -> Self {
+        Self {
+            data,
+            payload_bytes: raw.len() as u64,
+            raw,
+            not_modified: false,
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Fetched<U> {
+        Fetched {
+            data: f(self.data),
+            raw: self.raw,
+            payload_bytes: self.payload_bytes,
+            not_modified: self.not_modified,
+        }
+    }
+}
+
+#[async_trait]
+pub trait CountryProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn fetch_countries(&self) -> Result<Fetched<Vec<UpstreamCountry>>, ApiError>;
+}
+
+#[async_trait]
+pub trait RateProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn fetch_rates(&self) -> Result<Fetched<ExchangeRates>, ApiError>;
+}
+
+fn host_name(base_url: &str) -> String {
+    reqwest::Url::parse(base_url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| base_url.to_string())
+}
@@ -0,0 +1,40 @@
+use crate::error::ApiError;
+use crate::models::{ExchangeRateApiResponse, ExchangeRates};
+use crate::services::external_api::UpstreamClient;
+use crate::services::providers::{host_name, Fetched, RateProvider};
+use async_trait::async_trait;
+
+const LATEST_USD_PATH: &str = "/v6/latest/USD";
+
+pub struct OpenErApiProvider {
+    client: UpstreamClient,
+    url: String,
+    name: String,
+}
+
+impl OpenErApiProvider {
+    pub fn new(client: UpstreamClient, base_url: &str) -> Self {
+        let base_url = base_url.trim_end_matches('/');
+
+        Self {
+            client,
+            url: format!("{}{}", base_url, LATEST_USD_PATH),
+            name: host_name(base_url),
+        }
+    }
+}
+
+#[async_trait]
+impl RateProvider for OpenErApiProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch_rates(&self) -> Result<Fetched<ExchangeRates>, ApiError> {
+        let response = self.client
+            .get_json::<ExchangeRateApiResponse>(&self.url, &self.name)
+            .await?;
+
+        Ok(response.map(|response| response.into_rates(&self.name)))
+    }
+}
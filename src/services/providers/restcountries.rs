@@ -0,0 +1,74 @@
+use crate::error::ApiError;
+use crate::models::{CountryApiResponse, CountryV3ApiResponse, UpstreamCountry};
+use crate::services::external_api::UpstreamClient;
+use crate::services::providers::{host_name, CountryProvider, Fetched};
+use async_trait::async_trait;
+
+const V2_PATH: &str = "/v2/all?fields=name,capital,region,population,flag,currencies";
+const V3_PATH: &str = "/v3.1/all?fields=name,capital,region,population,flags,currencies";
+
+pub struct RestCountriesV2Provider {
+    client: UpstreamClient,
+    url: String,
+    name: String,
+}
+
+impl RestCountriesV2Provider {
+    pub fn new(client: UpstreamClient, base_url: &str) -> Self {
+        let base_url = base_url.trim_end_matches('/');
+
+        Self {
+            client,
+            url: format!("{}{}", base_url, V2_PATH),
+            name: host_name(base_url),
+        }
+    }
+}
+
+#[async_trait]
+impl CountryProvider for RestCountriesV2Provider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch_countries(&self) -> Result<Fetched<Vec<UpstreamCountry>>, ApiError> {
+        let countries = self.client
+            .get_json::<Vec<CountryApiResponse>>(&self.url, &self.name)
+            .await?;
+
+        Ok(countries.map(|countries| countries.into_iter().map(UpstreamCountry::from).collect()))
+    }
+}
+
+pub struct RestCountriesV3Provider {
+    client: UpstreamClient,
+    url: String,
+    name: String,
+}
+
+impl RestCountriesV3Provider {
+    pub fn new(client: UpstreamClient, base_url: &str) -> Self {
+        let base_url = base_url.trim_end_matches('/');
+
+        Self {
+            client,
+            url: format!("{}{}", base_url, V3_PATH),
+            name: host_name(base_url),
+        }
+    }
+}
+
+#[async_trait]
+impl CountryProvider for RestCountriesV3Provider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch_countries(&self) -> Result<Fetched<Vec<UpstreamCountry>>, ApiError> {
+        let countries = self.client
+            .get_json::<Vec<CountryV3ApiResponse>>(&self.url, &self.name)
+            .await?;
+
+        Ok(countries.map(|countries| countries.into_iter().map(UpstreamCountry::from).collect()))
+    }
+}
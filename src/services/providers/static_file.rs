@@ -0,0 +1,59 @@
+use crate::error::ApiError;
+use crate::models::{CountryApiResponse, ExchangeRateApiResponse, ExchangeRates, UpstreamCountry};
+use crate::services::providers::{CountryProvider, Fetched, RateProvider};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
+
+// Reads data saved in the upstream formats: a restcountries v2 array for
+// countries and an open.er-api response for rates.
+pub struct StaticFileProvider {
+    path: PathBuf,
+    name: String,
+}
+
+impl StaticFileProvider {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let name = path.display().to_string();
+
+        Self { path, name }
+    }
+
+    async fn read_json<T: DeserializeOwned>(&self) -> Result<Fetched<T>, ApiError> {
+        let bytes = tokio::fs::read(&self.path)
+            .await
+            .map_err(|_| ApiError::ExternalApi(self.name.clone()))?;
+
+        let data = serde_json::from_slice::<T>(&bytes)
+            .map_err(|_| ApiError::ExternalApi(self.name.clone()))?;
+
+        Ok(Fetched::new(data, bytes))
+    }
+}
+
+#[async_trait]
+impl CountryProvider for StaticFileProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch_countries(&self) -> Result<Fetched<Vec<UpstreamCountry>>, ApiError> {
+        let countries = self.read_json::<Vec<CountryApiResponse>>().await?;
+
+        Ok(countries.map(|countries| countries.into_iter().map(UpstreamCountry::from).collect()))
+    }
+}
+
+#[async_trait]
+impl RateProvider for StaticFileProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch_rates(&self) -> Result<Fetched<ExchangeRates>, ApiError> {
+        let response = self.read_json::<ExchangeRateApiResponse>().await?;
+
+        Ok(response.map(|response| response.into_rates(&self.name)))
+    }
+}
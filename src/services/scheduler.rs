@@ -0,0 +1,273 @@
+use crate::config::Config;
+use crate::db::repository;
+use crate::error::ApiError;
+use crate::models::{RefreshJobStatus, RefreshTrigger};
+use crate::services::{CountryService, RefreshSource};
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use rand::Rng;
+use sqlx::{MySql, Pool};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+pub enum RefreshSchedule {
+    Interval(Duration),
+    Cron(Box<Schedule>),
+}
+
+impl RefreshSchedule {
+    pub fn from_config(config: &Config) -> Result<Option<Self>, String> {
+        if let Some(expression) = &config.refresh_cron {
+            let schedule = parse_cron(expression)
+                .map_err(|e| format!("REFRESH_CRON must be a valid cron expression: {}", e))?;
+            return Ok(Some(RefreshSchedule::Cron(Box::new(schedule))));
+        }
+
+        Ok(config
+            .refresh_interval_secs
+            .filter(|&secs| secs > 0)
+            .map(|secs| RefreshSchedule::Interval(Duration::from_secs(secs))))
+    }
+
+    fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            RefreshSchedule::Interval(interval) => {
+                Some(after + chrono::Duration::from_std(*interval).ok()?)
+            }
+            RefreshSchedule::Cron(schedule) => schedule.after(&after).next(),
+        }
+    }
+}
+
+// The cron crate wants a leading seconds field; standard five-field
+// expressions are run at second 0. It also numbers weekdays 1-7 from Sunday,
+// so a standard 0-7 day-of-week field (0 and 7 both Sunday) is rewritten
+// first. Six- and seven-field expressions are passed through in the crate's
+// own syntax.
+fn parse_cron(expression: &str) -> Result<Schedule, String> {
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+
+    let expression = match fields.as_slice() {
+        [minute, hour, day, month, weekday] => {
+            format!("0 {} {} {} {} {}", minute, hour, day, month, cron_weekdays(weekday)?)
+        }
+        _ => expression.trim().to_string(),
+    };
+
+    Schedule::from_str(&expression).map_err(|e| e.to_string())
+}
+
+// Expands each numeric item of a standard day-of-week field into the
+// Sunday-first 1-7 days the cron crate expects. Named days (MON-FRI) already
+// mean the same thing to both and are left alone.
+fn cron_weekdays(field: &str) -> Result<String, String> {
+    if field == "*" || field == "?" {
+        return Ok(field.to_string());
+    }
+
+    let mut items = Vec::new();
+    let mut days = Vec::new();
+
+    for item in field.split(',') {
+        if item.chars().any(|c| c.is_ascii_alphabetic()) {
+            items.push(item.to_string());
+            continue;
+        }
+
+        let invalid = || format!("invalid day of week '{}', expected 0-7 with 0 and 7 both Sunday", item);
+        let day = |value: &str| value.parse::<u8>().ok().filter(|&day| day <= 7).ok_or_else(invalid);
+
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step = step.parse::<u8>().ok().filter(|&step| step > 0).ok_or_else(invalid)?;
+                (range, Some(step))
+            }
+            None => (item, None),
+        };
+
+        let (first, last) = match range.split_once('-') {
+            Some((first, last)) => (day(first)?, day(last)?),
+            None if range == "*" => (0, 6),
+            // `a/n` runs from a to the end of the week, as in standard cron.
+            None if step.is_some() => (day(range)?, 6),
+            None => (day(range)?, day(range)?),
+        };
+        if first > last {
+            return Err(invalid());
+        }
+
+        days.extend((first..=last).step_by(step.unwrap_or(1) as usize).map(|day| day % 7 + 1));
+    }
+
+    days.sort_unstable();
+    days.dedup();
+    items.extend(days.iter().map(u8::to_string));
+
+    Ok(items.join(","))
+}
+
+#[derive(Default)]
+pub struct SchedulerState {
+    next_run_at: RwLock<Option<DateTime<Utc>>>,
+}
+
+impl SchedulerState {
+    pub fn next_run_at(&self) -> Option<DateTime<Utc>> {
+        *self.next_run_at.read().unwrap()
+    }
+
+    fn set_next_run_at(&self, next_run_at: Option<DateTime<Utc>>) {
+        *self.next_run_at.write().unwrap() = next_run_at;
+    }
+}
+
+pub struct RefreshScheduler {
+    schedule: RefreshSchedule,
+    jitter_secs: u64,
+    service: Arc<CountryService>,
+}
+
+impl RefreshScheduler {
+    pub fn new(schedule: RefreshSchedule, config: &Config, service: Arc<CountryService>) -> Self {
+        Self {
+            schedule,
+            jitter_secs: config.refresh_jitter_secs,
+            service,
+        }
+    }
+
+    pub async fn run(self, pool: Pool<MySql>, state: &SchedulerState) {
+        loop {
+            let Some(next_run_at) = self.next_run_at() else {
+                log::warn!("Refresh schedule has no upcoming runs, stopping scheduler");
+                state.set_next_run_at(None);
+                return;
+            };
+
+            state.set_next_run_at(Some(next_run_at));
+            log::info!("Next scheduled refresh at {}", next_run_at.to_rfc3339());
+
+            let wait = (next_run_at - Utc::now()).to_std().unwrap_or(Duration::ZERO);
+            tokio::time::sleep(wait).await;
+
+            self.run_once(&pool).await;
+        }
+    }
+
+    fn next_run_at(&self) -> Option<DateTime<Utc>> {
+        let next = self.schedule.next_after(Utc::now())?;
+
+        if self.jitter_secs == 0 {
+            return Some(next);
+        }
+
+        let jitter = rand::thread_rng().gen_range(0..=self.jitter_secs);
+        Some(next + chrono::Duration::seconds(jitter as i64))
+    }
+
+    async fn run_once(&self, pool: &Pool<MySql>) {
+        let lock = CountryService::acquire_refresh_lock(pool).await;
+
+        let job_id = match repository::create_refresh_job(pool, RefreshTrigger::Scheduled, None).await {
+            Ok(id) => id,
+            Err(e) => {
+                log::error!("Failed to create scheduled refresh job: {:?}", e);
+                return;
+            }
+        };
+
+        let (status, reason) = match lock {
+            Ok(lock) => {
+                self.service.run_refresh_job(pool, job_id, lock, RefreshSource::Upstream).await;
+                return;
+            }
+            Err(ApiError::RefreshInProgress { job_id: running, .. }) => {
+                log::info!("Skipping scheduled refresh job {}: refresh {:?} is already in progress", job_id, running);
+                (RefreshJobStatus::Skipped, "A refresh was already in progress".to_string())
+            }
+            Err(e) => {
+                log::error!("Failed to acquire refresh lock: {:?}", e);
+                (RefreshJobStatus::Failed, e.detail())
+            }
+        };
+
+        if let Err(e) = repository::finish_refresh_job(pool, job_id, status, Some(&reason)).await {
+            log::error!("Failed to record outcome of refresh job {}: {:?}", job_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn five_field_cron_runs_at_second_zero() {
+        let schedule = parse_cron("0 * * * *").unwrap();
+        let after = Utc.with_ymd_and_hms(2024, 1, 1, 10, 15, 30).unwrap();
+
+        assert_eq!(
+            schedule.after(&after).next(),
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 11, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn six_field_cron_is_used_as_is() {
+        let schedule = parse_cron("30 0 * * * *").unwrap();
+        let after = Utc.with_ymd_and_hms(2024, 1, 1, 10, 15, 30).unwrap();
+
+        assert_eq!(
+            schedule.after(&after).next(),
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 11, 0, 30).unwrap())
+        );
+    }
+
+    #[test]
+    fn invalid_cron_is_an_error() {
+        assert!(parse_cron("every hour").is_err());
+        assert!(parse_cron("61 * * * *").is_err());
+        assert_eq!(
+            parse_cron("0 3 * * 8").unwrap_err(),
+            "invalid day of week '8', expected 0-7 with 0 and 7 both Sunday"
+        );
+        assert!(parse_cron("0 3 * * 5-1").is_err());
+    }
+
+    fn upcoming(expression: &str, count: usize) -> Vec<DateTime<Utc>> {
+        // 2024-01-06 is a Saturday.
+        let after = Utc.with_ymd_and_hms(2024, 1, 6, 12, 0, 0).unwrap();
+        parse_cron(expression).unwrap().after(&after).take(count).collect()
+    }
+
+    fn at_three(days: &[u32]) -> Vec<DateTime<Utc>> {
+        days.iter()
+            .map(|&day| Utc.with_ymd_and_hms(2024, 1, day, 3, 0, 0).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn weekday_ranges_use_standard_numbering() {
+        // Monday 8th to Friday 12th, then Monday 15th; never the weekend.
+        assert_eq!(upcoming("0 3 * * 1-5", 6), at_three(&[8, 9, 10, 11, 12, 15]));
+        assert_eq!(upcoming("0 3 * * MON-FRI", 6), at_three(&[8, 9, 10, 11, 12, 15]));
+    }
+
+    #[test]
+    fn zero_and_seven_are_both_sunday() {
+        assert_eq!(upcoming("0 3 * * 0", 2), at_three(&[7, 14]));
+        assert_eq!(upcoming("0 3 * * 7", 2), at_three(&[7, 14]));
+        assert_eq!(upcoming("0 3 * * 5-7", 4), at_three(&[7, 12, 13, 14]));
+    }
+
+    #[test]
+    fn weekday_lists_and_steps_are_expanded() {
+        assert_eq!(cron_weekdays("*/2").unwrap(), "1,3,5,7");
+        assert_eq!(cron_weekdays("1/3").unwrap(), "2,5");
+        assert_eq!(cron_weekdays("6,0").unwrap(), "1,7");
+        assert_eq!(cron_weekdays("*").unwrap(), "*");
+        assert_eq!(upcoming("0 3 * * 6,0", 2), at_three(&[7, 13]));
+    }
+}
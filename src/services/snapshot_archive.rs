@@ -0,0 +1,175 @@
+use crate::error::ApiError;
+use crate::models::{
+    CountryApiResponse, CountryV3ApiResponse, ExchangeRateApiResponse, UpstreamCountry,
+    UpstreamFetchStats,
+};
+use crate::services::external_api::UpstreamData;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Deserialize;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotKind {
+    Countries,
+    Rates,
+}
+
+impl SnapshotKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "countries" => Some(SnapshotKind::Countries),
+            "rates" => Some(SnapshotKind::Rates),
+            _ => None,
+        }
+    }
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            SnapshotKind::Countries => "countries.json.gz",
+            SnapshotKind::Rates => "rates.json.gz",
+        }
+    }
+}
+
+// Snapshots keep whichever restcountries shape was fetched, so replay accepts both.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ArchivedCountry {
+    V2(CountryApiResponse),
+    V3(CountryV3ApiResponse),
+}
+
+impl From<ArchivedCountry> for UpstreamCountry {
+    fn from(country: ArchivedCountry) -> Self {
+        match country {
+            ArchivedCountry::V2(country) => country.into(),
+            ArchivedCountry::V3(country) => country.into(),
+        }
+    }
+}
+
+pub struct SnapshotArchive {
+    dir: PathBuf,
+    retention: usize,
+}
+
+impl SnapshotArchive {
+    pub fn new(dir: impl Into<PathBuf>, retention: usize) -> Self {
+        Self {
+            dir: dir.into(),
+            retention,
+        }
+    }
+
+    pub async fn store(&self, job_id: u64, data: &UpstreamData) -> Result<(), ApiError> {
+        if self.retention == 0 {
+            return Ok(());
+        }
+
+        let job_dir = self.job_dir(job_id);
+        tokio::fs::create_dir_all(&job_dir).await?;
+
+        for (kind, raw) in [
+            (SnapshotKind::Countries, &data.raw_countries),
+            (SnapshotKind::Rates, &data.raw_rates),
+        ] {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(raw)?;
+            tokio::fs::write(job_dir.join(kind.file_name()), encoder.finish()?).await?;
+        }
+
+        self.prune().await
+    }
+
+    pub async fn read_compressed(&self, job_id: u64, kind: SnapshotKind) -> Option<Vec<u8>> {
+        tokio::fs::read(self.job_dir(job_id).join(kind.file_name())).await.ok()
+    }
+
+    pub async fn exists(&self, job_id: u64) -> bool {
+        for kind in [SnapshotKind::Countries, SnapshotKind::Rates] {
+            if !tokio::fs::try_exists(self.job_dir(job_id).join(kind.file_name()))
+                .await
+                .unwrap_or(false)
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub async fn load(&self, job_id: u64) -> Result<UpstreamData, ApiError> {
+        let source = format!("snapshot:{}", job_id);
+
+        let started = Instant::now();
+        let raw_countries = self.read_raw(job_id, SnapshotKind::Countries).await?;
+        let countries: Vec<ArchivedCountry> = serde_json::from_slice(&raw_countries)
+            .map_err(|_| ApiError::ExternalApi(source.clone()))?;
+        let countries_stats = UpstreamFetchStats {
+            source: source.clone(),
+            latency_ms: started.elapsed().as_millis() as u64,
+            payload_bytes: raw_countries.len() as u64,
+            not_modified: false,
+            error: None,
+        };
+
+        let started = Instant::now();
+        let raw_rates = self.read_raw(job_id, SnapshotKind::Rates).await?;
+        let rates: ExchangeRateApiResponse = serde_json::from_slice(&raw_rates)
+            .map_err(|_| ApiError::ExternalApi(source.clone()))?;
+        let rates_stats = UpstreamFetchStats {
+            source: source.clone(),
+            latency_ms: started.elapsed().as_millis() as u64,
+            payload_bytes: raw_rates.len() as u64,
+            not_modified: false,
+            error: None,
+        };
+
+        Ok(UpstreamData {
+            countries: countries.into_iter().map(UpstreamCountry::from).collect(),
+            rates: rates.into_rates(&source),
+            stats: vec![countries_stats, rates_stats],
+            raw_countries,
+            raw_rates,
+        })
+    }
+
+    async fn read_raw(&self, job_id: u64, kind: SnapshotKind) -> Result<Vec<u8>, ApiError> {
+        let compressed = self
+            .read_compressed(job_id, kind)
+            .await
+            .ok_or(ApiError::SnapshotNotFound)?;
+
+        let mut raw = Vec::new();
+        GzDecoder::new(compressed.as_slice()).read_to_end(&mut raw)?;
+        Ok(raw)
+    }
+
+    async fn prune(&self) -> Result<(), ApiError> {
+        let mut job_ids = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.dir).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(job_id) = entry.file_name().to_str().and_then(|name| name.parse::<u64>().ok()) {
+                job_ids.push(job_id);
+            }
+        }
+
+        job_ids.sort_unstable_by(|a, b| b.cmp(a));
+
+        for job_id in job_ids.into_iter().skip(self.retention) {
+            log::info!("Removing snapshot for refresh job {}", job_id);
+            tokio::fs::remove_dir_all(self.job_dir(job_id)).await?;
+        }
+
+        Ok(())
+    }
+
+    fn job_dir(&self, job_id: u64) -> PathBuf {
+        self.dir.join(job_id.to_string())
+    }
+}
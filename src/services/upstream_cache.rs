@@ -0,0 +1,90 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntryMeta {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    fetched_at: DateTime<Utc>,
+}
+
+pub struct CachedResponse {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Clone)]
+pub struct UpstreamCache {
+    dir: PathBuf,
+}
+
+impl UpstreamCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub async fn load(&self, url: &str) -> Option<CachedResponse> {
+        let (meta_path, body_path) = self.paths(url);
+
+        let meta = tokio::fs::read(&meta_path).await.ok()?;
+        let meta: CacheEntryMeta = serde_json::from_slice(&meta).ok()?;
+        if meta.url != url {
+            return None;
+        }
+
+        let body = tokio::fs::read(&body_path).await.ok()?;
+
+        Some(CachedResponse {
+            etag: meta.etag,
+            last_modified: meta.last_modified,
+            body,
+        })
+    }
+
+    pub async fn store(
+        &self,
+        url: &str,
+        etag: Option<String>,
+        last_modified: Option<String>,
+        body: &[u8],
+    ) {
+        if etag.is_none() && last_modified.is_none() {
+            return;
+        }
+
+        let (meta_path, body_path) = self.paths(url);
+        let meta = CacheEntryMeta {
+            url: url.to_string(),
+            etag,
+            last_modified,
+            fetched_at: Utc::now(),
+        };
+
+        let result = async {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            tokio::fs::write(&body_path, body).await?;
+            tokio::fs::write(&meta_path, serde_json::to_vec(&meta)?).await?;
+            Ok::<_, Box<dyn std::error::Error>>(())
+        }
+        .await;
+
+        if let Err(e) = result {
+            log::warn!("Failed to cache response for {}: {}", url, e);
+        }
+    }
+
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let key: String = url
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+
+        (
+            self.dir.join(format!("{}.meta.json", key)),
+            self.dir.join(format!("{}.body", key)),
+        )
+    }
+}
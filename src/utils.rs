@@ -0,0 +1,53 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+// Accepts either an RFC 3339 timestamp or a plain `YYYY-MM-DD` date. Plain
+// dates resolve to the start of the day, or its last second when `end_of_day`
+// is set so that `to=2024-01-31` includes the whole of that day.
+pub fn parse_datetime_param(value: &str, end_of_day: bool) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    let time = if end_of_day {
+        date.and_hms_opt(23, 59, 59)?
+    } else {
+        date.and_hms_opt(0, 0, 0)?
+    };
+
+    Some(time.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn plain_dates_resolve_to_the_start_or_end_of_the_day() {
+        assert_eq!(
+            parse_datetime_param("2024-01-31", false),
+            Some(Utc.with_ymd_and_hms(2024, 1, 31, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            parse_datetime_param("2024-01-31", true),
+            Some(Utc.with_ymd_and_hms(2024, 1, 31, 23, 59, 59).unwrap())
+        );
+    }
+
+    #[test]
+    fn timestamps_are_converted_to_utc_and_ignore_end_of_day() {
+        let expected = Some(Utc.with_ymd_and_hms(2024, 1, 31, 10, 30, 0).unwrap());
+
+        assert_eq!(parse_datetime_param("2024-01-31T12:30:00+02:00", false), expected);
+        assert_eq!(parse_datetime_param("2024-01-31T10:30:00Z", true), expected);
+    }
+
+    #[test]
+    fn other_formats_are_rejected() {
+        assert_eq!(parse_datetime_param("2024-02-30", false), None);
+        assert_eq!(parse_datetime_param("31/01/2024", false), None);
+        assert_eq!(parse_datetime_param("2024-01-31 10:30:00", false), None);
+        assert_eq!(parse_datetime_param("", false), None);
+    }
+}